@@ -0,0 +1,16 @@
+#!/usr/bin/env sh
+# Regenerates src-tauri/resources/common-passwords.txt from the SecLists
+# top-10k list: lowercased, deduplicated, most common first.
+set -eu
+
+SOURCE_URL="https://raw.githubusercontent.com/danielmiessler/SecLists/master/Passwords/Common-Credentials/10k-most-common.txt"
+TARGET="$(dirname "$0")/../src-tauri/resources/common-passwords.txt"
+
+curl -fsSL "$SOURCE_URL" \
+    | tr -d '\r' \
+    | tr '[:upper:]' '[:lower:]' \
+    | awk 'NF && !seen[$0]++' \
+    > "$TARGET.tmp"
+mv "$TARGET.tmp" "$TARGET"
+
+echo "Wrote $(wc -l < "$TARGET") passwords to $TARGET"
@@ -0,0 +1,19 @@
+#!/usr/bin/env sh
+# Regenerates src-tauri/resources/totp-sites.txt from the 2fa.directory API:
+# every domain (including additional domains) of sites offering TOTP.
+set -eu
+
+SOURCE_URL="https://api.2fa.directory/v3/totp.json"
+TARGET="$(dirname "$0")/../src-tauri/resources/totp-sites.txt"
+
+{
+    echo "# Domains with TOTP 2FA support, extracted from the 2fa.directory dataset"
+    curl -fsSL "$SOURCE_URL" \
+        | jq -r '.[] | .[1] | .domain, (.["additional-domains"] // [])[]' \
+        | tr '[:upper:]' '[:lower:]' \
+        | sed 's/^www\.//' \
+        | sort -u
+} > "$TARGET.tmp"
+mv "$TARGET.tmp" "$TARGET"
+
+echo "Wrote $(grep -vc '^#' "$TARGET") domains to $TARGET"
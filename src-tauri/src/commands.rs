@@ -1,10 +1,14 @@
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use tauri::tray::{TrayIconBuilder, TrayIconEvent};
+use tauri::menu::{Menu, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
 use tauri::{Emitter, Manager, RunEvent, WindowEvent};
 use tauri_plugin_autostart::MacosLauncher;
 use tauri_plugin_autostart::ManagerExt;
+use tauri_plugin_clipboard_manager::ClipboardExt;
 use tauri_plugin_global_shortcut::ShortcutState;
 
 #[cfg(target_os = "macos")]
@@ -47,6 +51,135 @@ struct PasswordEntry {
     created_at: String,
     modified_at: String,
     password_strength: u8,
+    // Older vaults have no `kind`; they load as plain logins
+    #[serde(default)]
+    kind: EntryKind,
+    // Base32 TOTP seed, uppercase without padding/spaces
+    #[serde(default)]
+    totp_secret: Option<String>,
+    #[serde(default = "default_totp_digits")]
+    totp_digits: u8,
+    #[serde(default = "default_totp_period")]
+    totp_period: u32,
+    #[serde(default)]
+    totp_algorithm: TotpAlgo,
+    // Previous passwords, oldest first
+    #[serde(default)]
+    password_history: Vec<PasswordHistoryItem>,
+    // Notes stay masked in the detail view until revealed
+    #[serde(default)]
+    notes_sensitive: bool,
+    // Launcher decoration: "#rrggbb" color and a short emoji/glyph
+    #[serde(default)]
+    color: Option<String>,
+    #[serde(default)]
+    glyph: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    // Position in the user's manual ordering; set by reorder_entries
+    #[serde(default)]
+    sort_index: Option<i32>,
+    #[serde(default)]
+    custom_fields: Vec<CustomField>,
+    // Deliberately hidden from search and listing, unlike a deletion
+    #[serde(default)]
+    archived: bool,
+    // When the current password was set. Edits to other fields bump
+    // modified_at but not this, so password age is measured from here.
+    #[serde(default)]
+    password_changed_at: Option<String>,
+}
+
+// Extra name/value pair. Only `searchable` fields take part in search and
+// appear in list/search previews; `sensitive` ones are masked there.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+struct CustomField {
+    name: String,
+    value: String,
+    #[serde(default)]
+    searchable: bool,
+    #[serde(default)]
+    sensitive: bool,
+}
+
+const MASKED_VALUE: &str = "••••••••";
+
+// What search results and list rows may show of an entry's custom fields
+fn custom_field_previews(fields: &[CustomField]) -> Vec<CustomField> {
+    fields
+        .iter()
+        .filter(|field| field.searchable)
+        .map(|field| CustomField {
+            value: if field.sensitive {
+                MASKED_VALUE.to_string()
+            } else {
+                field.value.clone()
+            },
+            ..field.clone()
+        })
+        .collect()
+}
+
+fn default_totp_digits() -> u8 {
+    6
+}
+
+fn default_totp_period() -> u32 {
+    30
+}
+
+impl Default for PasswordEntry {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            title: String::new(),
+            username: String::new(),
+            password: String::new(),
+            url: None,
+            notes: None,
+            created_at: String::new(),
+            modified_at: String::new(),
+            password_strength: 0,
+            kind: EntryKind::default(),
+            totp_secret: None,
+            totp_digits: default_totp_digits(),
+            totp_period: default_totp_period(),
+            totp_algorithm: TotpAlgo::default(),
+            password_history: Vec::new(),
+            notes_sensitive: false,
+            color: None,
+            glyph: None,
+            tags: Vec::new(),
+            sort_index: None,
+            custom_fields: Vec::new(),
+            archived: false,
+            password_changed_at: None,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+struct PasswordHistoryItem {
+    password: String,
+    // When this password was replaced by a newer one
+    replaced_at: String,
+}
+
+// Exported WebAuthn credential material; signing is not implemented yet
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+struct PasskeyCredential {
+    rp_id: String,
+    user_handle: String,
+    credential_id: String,
+    private_key: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum EntryKind {
+    #[default]
+    Login,
+    Passkey(PasskeyCredential),
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -56,6 +189,19 @@ struct EncryptedPasswordStore {
     salt: String,
     iterations: u32,
     version: u8,
+    // Present when the vault also needs a keyfile to open
+    #[serde(default)]
+    keyfile_check: Option<String>,
+    // Bumped on every save; the search index records the revision it was built from
+    #[serde(default)]
+    revision: u64,
+    // Plaintext was gzipped before encryption; older vaults are stored raw
+    #[serde(default)]
+    compressed: bool,
+    // Argon2 cost the key was derived with ("m=..,t=..,p=.."), so a backup
+    // still opens after a rekey. Older vaults don't record it and used the default.
+    #[serde(default)]
+    kdf_params: Option<String>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -91,6 +237,140 @@ lazy_static::lazy_static! {
     }));
 }
 
+// Set by `cancel_autofill`, cleared whenever a new autofill starts
+static AUTOFILL_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+// Per-keystroke delay from the active profile; 0 means the platform default
+static KEYSTROKE_DELAY_MS: AtomicU64 = AtomicU64::new(0);
+
+fn begin_autofill(action: &str) {
+    AUTOFILL_CANCELLED.store(false, Ordering::SeqCst);
+
+    let profile = active_autofill_profile();
+    KEYSTROKE_DELAY_MS.store(profile.typing_delay_ms.unwrap_or(0), Ordering::SeqCst);
+    tracing::info!(action, backend = INPUT_BACKEND, "Autofill started");
+}
+
+fn keystroke_delay(platform_default_ms: u64) -> std::time::Duration {
+    match KEYSTROKE_DELAY_MS.load(Ordering::SeqCst) {
+        0 => std::time::Duration::from_millis(platform_default_ms),
+        delay_ms => std::time::Duration::from_millis(delay_ms),
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum FillStep {
+    Username,
+    Tab,
+    Password,
+}
+
+// Named timing/sequence presets, e.g. a fast local profile and a slow one for RDP
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(default)]
+struct AutofillProfile {
+    typing_delay_ms: Option<u64>,
+    // How long to wait for focus to return to the target app before typing
+    settle_ms: Option<u64>,
+    field_sequence: Vec<FillStep>,
+    // Only the platform's built-in backend exists today; recorded for later backends
+    backend: Option<String>,
+    press_enter: bool,
+}
+
+impl Default for AutofillProfile {
+    fn default() -> Self {
+        Self {
+            typing_delay_ms: None,
+            settle_ms: None,
+            field_sequence: vec![FillStep::Username, FillStep::Tab, FillStep::Password],
+            backend: None,
+            press_enter: false,
+        }
+    }
+}
+
+fn active_autofill_profile() -> AutofillProfile {
+    let settings = load_settings().unwrap_or_default();
+
+    settings
+        .active_autofill_profile
+        .and_then(|name| settings.autofill_profiles.get(&name).cloned())
+        .unwrap_or_default()
+}
+
+#[tauri::command]
+async fn save_autofill_profile(name: String, profile: AutofillProfile) -> Result<(), String> {
+    if let Some(backend) = &profile.backend {
+        if backend != INPUT_BACKEND {
+            return Err(format!(
+                "Input backend '{}' is not available; this build uses '{}'",
+                backend, INPUT_BACKEND
+            ));
+        }
+    }
+    if profile.field_sequence.is_empty() {
+        return Err("Field sequence must not be empty".to_string());
+    }
+
+    let mut settings = load_settings()?;
+    settings.autofill_profiles.insert(name, profile);
+    save_settings(&settings)
+}
+
+// None switches back to the built-in defaults
+#[tauri::command]
+async fn set_active_autofill_profile(name: Option<String>) -> Result<(), String> {
+    let mut settings = load_settings()?;
+
+    if let Some(name) = &name {
+        if !settings.autofill_profiles.contains_key(name) {
+            return Err(format!("Autofill profile '{}' not found", name));
+        }
+    }
+
+    settings.active_autofill_profile = name;
+    save_settings(&settings)
+}
+
+// Checked between keystrokes so a runaway fill can be stopped mid-string
+fn ensure_autofill_active() -> Result<(), String> {
+    if AUTOFILL_CANCELLED.load(Ordering::SeqCst) {
+        tracing::info!("Autofill cancelled");
+        release_modifier_keys();
+        return Err("Autofill cancelled".to_string());
+    }
+    Ok(())
+}
+
+// Make sure no modifier is left logically held after an aborted fill
+fn release_modifier_keys() {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        use winapi::um::winuser::{VK_CONTROL, VK_MENU, VK_SHIFT};
+
+        for vk in [VK_SHIFT, VK_CONTROL, VK_MENU] {
+            let mut input = INPUT {
+                type_: INPUT_KEYBOARD,
+                u: std::mem::zeroed(),
+            };
+            input.u.ki_mut().wVk = vk as u16;
+            input.u.ki_mut().dwFlags = KEYEVENTF_KEYUP;
+            SendInput(1, &mut input, std::mem::size_of::<INPUT>() as i32);
+        }
+    }
+
+    // macOS and X11 typing posts unicode/plain key events without modifier
+    // flags, so there is nothing held down to release there.
+}
+
+#[tauri::command]
+async fn cancel_autofill() -> Result<(), String> {
+    AUTOFILL_CANCELLED.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
 // Enhanced macOS focus management
 #[cfg(target_os = "macos")]
 fn capture_current_focus() -> Result<(), String> {
@@ -127,6 +407,72 @@ fn restore_target_focus() -> Result<(), String> {
     Ok(())
 }
 
+#[derive(serde::Serialize)]
+struct TargetWindow {
+    title: String,
+    pid: u32,
+    bundle_id: Option<String>,
+}
+
+// Regular (Dock-visible) apps other than Cocoon, as candidates for retargeting.
+// Titles are app names: window titles would need Screen Recording permission.
+#[tauri::command]
+async fn list_target_windows() -> Result<Vec<TargetWindow>, String> {
+    #[cfg(target_os = "macos")]
+    {
+        use objc2_app_kit::{NSApplicationActivationPolicy, NSWorkspace};
+
+        let own_pid = std::process::id();
+        let mut targets = Vec::new();
+        unsafe {
+            for app in NSWorkspace::sharedWorkspace().runningApplications().iter() {
+                let pid = app.processIdentifier() as u32;
+                if pid == own_pid
+                    || app.isTerminated()
+                    || app.activationPolicy() != NSApplicationActivationPolicy::Regular
+                {
+                    continue;
+                }
+                targets.push(TargetWindow {
+                    title: app.localizedName().map(|name| name.to_string()).unwrap_or_default(),
+                    pid,
+                    bundle_id: app.bundleIdentifier().map(|id| id.to_string()),
+                });
+            }
+        }
+        Ok(targets)
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        Err("Choosing the autofill target is only available on macOS".to_string())
+    }
+}
+
+// Overrides the app captured when the shortcut fired; used by the next autofill
+#[tauri::command]
+async fn set_autofill_target(pid: u32) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        use objc2_app_kit::NSRunningApplication;
+
+        let app = unsafe { NSRunningApplication::runningApplicationWithProcessIdentifier(pid as i32) }
+            .ok_or_else(|| format!("No running application with pid {}", pid))?;
+
+        let mut focus_state = FOCUS_STATE.lock().unwrap();
+        focus_state.target_app_pid = Some(pid);
+        focus_state.last_active_window = unsafe { app.bundleIdentifier() }.map(|id| id.to_string());
+        tracing::info!(pid, "Autofill target changed");
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = pid;
+        Err("Choosing the autofill target is only available on macOS".to_string())
+    }
+}
+
 // Enhanced window configuration for better Spotlight-like behavior
 #[cfg(target_os = "macos")]
 fn configure_spotlight_panel(window: &tauri::WebviewWindow) -> Result<(), String> {
@@ -170,30 +516,76 @@ fn simulate_typing_with_focus_restore(text: &str) -> Result<(), String> {
         .map_err(|_| "Failed to create event source")?;
 
     for ch in text.chars() {
+        ensure_autofill_active()?;
+
         if let Ok(event) = CGEvent::new_keyboard_event(source.clone(), 0, true) {
             event.set_string_from_utf16_unchecked(&[ch as u16]);
             event.post(CGEventTapLocation::HID);
-            std::thread::sleep(std::time::Duration::from_millis(15));
+            std::thread::sleep(keystroke_delay(15));
         }
     }
 
     Ok(())
 }
 
+// Virtual keycodes from HIToolbox Events.h (kVK_Return / kVK_Tab); these are
+// positional and identical on every macOS keyboard layout
+#[cfg(target_os = "macos")]
+const KVK_RETURN: u16 = 0x24;
+#[cfg(target_os = "macos")]
+const KVK_TAB: u16 = 0x30;
+
+// Hardware scan code for a virtual key, so apps reading scan codes (RDP, some
+// games/toolkits) see the same key as apps reading the virtual key
+#[cfg(target_os = "windows")]
+fn windows_scan_code(vk: i32) -> u16 {
+    use winapi::um::winuser::{MapVirtualKeyW, MAPVK_VK_TO_VSC};
+
+    unsafe { MapVirtualKeyW(vk as u32, MAPVK_VK_TO_VSC) as u16 }
+}
+
+// evdev keycodes (kernel code + 8) used when the active keymap has no entry
+#[cfg(target_os = "linux")]
+const X11_FALLBACK_KEYCODES: &[(u32, u32)] = &[(x11::keysym::XK_Return, 36), (x11::keysym::XK_Tab, 23)];
+
+#[cfg(target_os = "linux")]
+fn x11_fallback_keycode(keysym: u32) -> Option<u32> {
+    X11_FALLBACK_KEYCODES
+        .iter()
+        .find(|(known, _)| *known == keysym)
+        .map(|(_, keycode)| *keycode)
+}
+
+// Resolve a keysym through the active keymap instead of assuming evdev keycodes
+#[cfg(target_os = "linux")]
+fn x11_keycode(display: *mut xlib::Display, keysym: u32) -> u32 {
+    let keycode = unsafe { xlib::XKeysymToKeycode(display, keysym as xlib::KeySym) };
+
+    if keycode == 0 {
+        let fallback = x11_fallback_keycode(keysym).unwrap_or(0);
+        tracing::warn!(keysym, fallback, "Keysym not mapped, using fallback keycode");
+        fallback
+    } else {
+        keycode as u32
+    }
+}
+
 #[cfg(target_os = "macos")]
 fn simulate_enter() -> Result<(), String> {
     use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
 
+    ensure_autofill_active()?;
+
     let source = CGEventSource::new(CGEventSourceStateID::HIDSystemState)
         .map_err(|_| "Failed to create event source")?;
 
-    // Enter key press (keycode 36 on macOS)
-    if let Ok(event) = CGEvent::new_keyboard_event(source.clone(), 36, true) {
+    // Enter key press
+    if let Ok(event) = CGEvent::new_keyboard_event(source.clone(), KVK_RETURN, true) {
         event.post(CGEventTapLocation::HID);
         std::thread::sleep(std::time::Duration::from_millis(50));
 
         // Enter key release
-        if let Ok(event_up) = CGEvent::new_keyboard_event(source, 36, false) {
+        if let Ok(event_up) = CGEvent::new_keyboard_event(source, KVK_RETURN, false) {
             event_up.post(CGEventTapLocation::HID);
         }
     }
@@ -205,6 +597,8 @@ fn simulate_enter() -> Result<(), String> {
 fn simulate_enter() -> Result<(), String> {
     use winapi::um::winuser::{SendInput, INPUT, INPUT_KEYBOARD, KEYEVENTF_KEYUP, VK_RETURN};
 
+    ensure_autofill_active()?;
+
     let mut input_down = INPUT {
         type_: INPUT_KEYBOARD,
         u: unsafe { std::mem::zeroed() },
@@ -218,10 +612,12 @@ fn simulate_enter() -> Result<(), String> {
     unsafe {
         // Enter key down
         input_down.u.ki_mut().wVk = VK_RETURN as u16;
+        input_down.u.ki_mut().wScan = windows_scan_code(VK_RETURN);
         input_down.u.ki_mut().dwFlags = 0;
 
         // Enter key up
         input_up.u.ki_mut().wVk = VK_RETURN as u16;
+        input_up.u.ki_mut().wScan = windows_scan_code(VK_RETURN);
         input_up.u.ki_mut().dwFlags = KEYEVENTF_KEYUP;
 
         if SendInput(1, &mut input_down, std::mem::size_of::<INPUT>() as i32) != 1 {
@@ -242,13 +638,15 @@ fn simulate_enter() -> Result<(), String> {
 fn simulate_enter() -> Result<(), String> {
     use std::ptr;
 
+    ensure_autofill_active()?;
+
     unsafe {
         let display = x11::xlib::XOpenDisplay(ptr::null());
         if display.is_null() {
             return Err("Failed to open X11 display".to_string());
         }
 
-        let enter_keycode = 36; // Enter key on most X11 systems
+        let enter_keycode = x11_keycode(display, x11::keysym::XK_Return);
 
         // Key press
         let mut event: x11::xlib::XKeyEvent = std::mem::zeroed();
@@ -282,162 +680,329 @@ fn simulate_enter() -> Result<(), String> {
     Ok(())
 }
 
-#[tauri::command]
-async fn auto_fill_and_login_spotlight(
-    entry_id: u32,
-    master_password: String,
-    app_handle: tauri::AppHandle,
-) -> Result<(), String> {
-    let store = load_password_store(&master_password)?;
+// Progress reported to the UI on the `autofill-status` event
+#[derive(serde::Serialize, Clone)]
+#[serde(tag = "stage")]
+enum AutofillStatus {
+    Hiding,
+    RestoringFocus,
+    TypingUsername,
+    TypingPassword,
+    TypingText,
+    PressingEnter,
+    Completed,
+    Failed { reason: String },
+}
 
-    if let Some(entry) = store.entries.iter().find(|e| e.id == entry_id) {
-        // Hide Cocoon window
-        if let Some(window) = app_handle.get_webview_window("main") {
-            let _ = window.hide();
+fn emit_autofill_status(app_handle: &tauri::AppHandle, status: AutofillStatus) {
+    let _ = app_handle.emit("autofill-status", status);
+}
+
+fn finish_autofill(app_handle: &tauri::AppHandle, result: Result<(), String>) -> Result<(), String> {
+    match &result {
+        Ok(()) => {
+            tracing::info!("Autofill finished");
+            emit_autofill_status(app_handle, AutofillStatus::Completed);
+        }
+        Err(reason) => {
+            tracing::warn!("Autofill failed: {}", reason);
+            emit_autofill_status(
+                app_handle,
+                AutofillStatus::Failed {
+                    reason: reason.clone(),
+                },
+            );
         }
+    }
+    result
+}
 
-        #[cfg(target_os = "macos")]
-        {
-            // Restore focus to target application
-            restore_target_focus()?;
-            std::thread::sleep(std::time::Duration::from_millis(200));
+fn hide_for_autofill(app_handle: &tauri::AppHandle) {
+    emit_autofill_status(app_handle, AutofillStatus::Hiding);
 
-            // Type credentials and login
-            simulate_typing_with_focus_restore(&entry.username)?;
-            simulate_tab()?;
-            std::thread::sleep(std::time::Duration::from_millis(100));
-            simulate_typing_with_focus_restore(&entry.password)?;
-            std::thread::sleep(std::time::Duration::from_millis(200));
-            simulate_enter()?; // Press Enter to login
-        }
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let _ = window.hide();
+    }
+}
 
-        #[cfg(not(target_os = "macos"))]
-        {
-            std::thread::sleep(std::time::Duration::from_millis(500));
-            simulate_typing(&entry.username)?;
-            simulate_tab()?;
-            std::thread::sleep(std::time::Duration::from_millis(100));
-            simulate_typing(&entry.password)?;
-            std::thread::sleep(std::time::Duration::from_millis(200));
-            simulate_enter()?; // Press Enter to login
-        }
+fn type_field(app_handle: &tauri::AppHandle, status: AutofillStatus, text: &str) -> Result<(), String> {
+    emit_autofill_status(app_handle, status);
+
+    // Only the typed value is trimmed; the stored secret is left alone
+    let text = if load_settings().map(|s| s.trim_on_fill).unwrap_or(true) {
+        text.trim()
     } else {
-        return Err("Entry not found".to_string());
+        text
+    };
+
+    #[cfg(target_os = "macos")]
+    {
+        simulate_typing_with_focus_restore(text)
     }
 
-    Ok(())
+    #[cfg(not(target_os = "macos"))]
+    {
+        simulate_typing(text)
+    }
 }
 
-#[tauri::command]
-async fn press_enter_after_autofill(_app_handle: tauri::AppHandle) -> Result<(), String> {
-    std::thread::sleep(std::time::Duration::from_millis(300));
-    
-    simulate_enter()?;
-    
+// Types the active profile's field sequence (username, Tab, password by
+// default) and optionally Enter into the previously focused app
+fn fill_credentials(
+    app_handle: &tauri::AppHandle,
+    entry: &PasswordEntry,
+    press_enter: bool,
+) -> Result<(), String> {
+    let profile = active_autofill_profile();
+
+    hide_for_autofill(app_handle);
+    emit_autofill_status(app_handle, AutofillStatus::RestoringFocus);
+
+    #[cfg(target_os = "macos")]
+    {
+        restore_target_focus()?;
+        std::thread::sleep(std::time::Duration::from_millis(profile.settle_ms.unwrap_or(200)));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    std::thread::sleep(std::time::Duration::from_millis(profile.settle_ms.unwrap_or(500)));
+
+    for step in &profile.field_sequence {
+        match step {
+            FillStep::Username => {
+                type_field(app_handle, AutofillStatus::TypingUsername, &entry.username)?
+            }
+            FillStep::Password => {
+                type_field(app_handle, AutofillStatus::TypingPassword, &entry.password)?
+            }
+            FillStep::Tab => {
+                simulate_tab()?;
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+        }
+    }
+
+    if press_enter {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        emit_autofill_status(app_handle, AutofillStatus::PressingEnter);
+        simulate_enter()?;
+    }
+
     Ok(())
 }
 
-// Enhanced commands with better focus management
+// `settle_ms` gives the window manager time to hand focus back on platforms
+// where we can't restore it explicitly
+fn fill_single_field(
+    app_handle: &tauri::AppHandle,
+    status: AutofillStatus,
+    text: &str,
+    settle_ms: u64,
+) -> Result<(), String> {
+    hide_for_autofill(app_handle);
+    emit_autofill_status(app_handle, AutofillStatus::RestoringFocus);
+
+    #[cfg(not(target_os = "macos"))]
+    std::thread::sleep(std::time::Duration::from_millis(
+        active_autofill_profile().settle_ms.unwrap_or(settle_ms),
+    ));
+    #[cfg(target_os = "macos")]
+    let _ = settle_ms;
+
+    type_field(app_handle, status, text)
+}
+
 #[tauri::command]
-async fn type_username_spotlight(
+async fn auto_fill_and_login_spotlight(
     entry_id: u32,
-    master_password: String,
+    master_password: Option<String>,
     app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
-    let store = load_password_store(&master_password)?;
+    let entry = find_entry(entry_id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+    begin_autofill("auto_fill_and_login_spotlight");
 
-    if let Some(entry) = store.entries.iter().find(|e| e.id == entry_id) {
-        // Hide Cocoon window
-        if let Some(window) = app_handle.get_webview_window("main") {
-            let _ = window.hide();
-        }
+    finish_autofill(&app_handle, fill_credentials(&app_handle, &entry, true))
+}
 
-        // Type with focus restoration
-        #[cfg(target_os = "macos")]
-        simulate_typing_with_focus_restore(&entry.username)?;
+#[tauri::command]
+async fn press_enter_after_autofill(app_handle: tauri::AppHandle) -> Result<(), String> {
+    begin_autofill("press_enter_after_autofill");
+    std::thread::sleep(std::time::Duration::from_millis(300));
 
-        #[cfg(not(target_os = "macos"))]
-        {
-            std::thread::sleep(std::time::Duration::from_millis(500));
-            simulate_typing(&entry.username)?;
+    emit_autofill_status(&app_handle, AutofillStatus::PressingEnter);
+    finish_autofill(&app_handle, simulate_enter())
+}
+
+// Split logins (username page, then password page): the first trigger types
+// the username, a second one within TWO_STEP_TTL types the password
+const TWO_STEP_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
+lazy_static::lazy_static! {
+    static ref PENDING_PASSWORD_STEPS: Mutex<HashMap<u32, std::time::Instant>> = Mutex::new(HashMap::new());
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum AutofillStep {
+    Username,
+    Password,
+}
+
+#[tauri::command]
+async fn autofill_step(
+    entry_id: u32,
+    master_password: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<AutofillStep, String> {
+    let entry = find_entry(entry_id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+
+    let step = {
+        let now = std::time::Instant::now();
+        let mut pending = PENDING_PASSWORD_STEPS.lock().unwrap();
+        pending.retain(|_, started| now.duration_since(*started) < TWO_STEP_TTL);
+
+        if pending.remove(&entry_id).is_some() {
+            AutofillStep::Password
+        } else {
+            pending.insert(entry_id, now);
+            AutofillStep::Username
         }
-    } else {
-        return Err("Entry not found".to_string());
-    }
+    };
 
-    Ok(())
+    begin_autofill("autofill_step");
+
+    let (status, text) = match step {
+        AutofillStep::Username => (AutofillStatus::TypingUsername, &entry.username),
+        AutofillStep::Password => (AutofillStatus::TypingPassword, &entry.password),
+    };
+    finish_autofill(&app_handle, fill_single_field(&app_handle, status, text, 500))?;
+
+    Ok(step)
 }
 
+// Enhanced commands with better focus management
 #[tauri::command]
-async fn type_password_spotlight(
+async fn type_username_spotlight(
     entry_id: u32,
-    master_password: String,
+    master_password: Option<String>,
     app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
-    let store = load_password_store(&master_password)?;
+    let entry = find_entry(entry_id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+    begin_autofill("type_username_spotlight");
+
+    let result = fill_single_field(
+        &app_handle,
+        AutofillStatus::TypingUsername,
+        &entry.username,
+        500,
+    );
+    finish_autofill(&app_handle, result)
+}
 
-    if let Some(entry) = store.entries.iter().find(|e| e.id == entry_id) {
-        // Hide Cocoon window
-        if let Some(window) = app_handle.get_webview_window("main") {
-            let _ = window.hide();
-        }
+#[tauri::command]
+async fn type_password_spotlight(
+    entry_id: u32,
+    master_password: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let entry = find_entry(entry_id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+    begin_autofill("type_password_spotlight");
+
+    let result = fill_single_field(
+        &app_handle,
+        AutofillStatus::TypingPassword,
+        &entry.password,
+        1000,
+    );
+    finish_autofill(&app_handle, result)
+}
 
-        // Type with focus restoration
-        #[cfg(target_os = "macos")]
-        simulate_typing_with_focus_restore(&entry.password)?;
+const MAX_TYPED_TEXT_CHARS: usize = 4096;
 
-        #[cfg(not(target_os = "macos"))]
-        {
-            std::thread::sleep(std::time::Duration::from_millis(1000));
-            simulate_typing(&entry.password)?;
-        }
-    } else {
-        return Err("Entry not found".to_string());
+// Types a value that isn't stored yet (e.g. a freshly generated password for a
+// signup form). The text may be a secret, so it's never logged.
+#[tauri::command]
+async fn type_text_spotlight(text: String, app_handle: tauri::AppHandle) -> Result<(), String> {
+    let text = zeroize::Zeroizing::new(text);
+    if text.chars().count() > MAX_TYPED_TEXT_CHARS {
+        return Err(format!("Text is longer than {} characters", MAX_TYPED_TEXT_CHARS));
     }
+    begin_autofill("type_text_spotlight");
 
-    Ok(())
+    let result = fill_single_field(&app_handle, AutofillStatus::TypingText, &text, 500);
+    finish_autofill(&app_handle, result)
 }
 
 #[tauri::command]
 async fn auto_fill_credentials_spotlight(
     entry_id: u32,
-    master_password: String,
+    master_password: Option<String>,
     app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
-    let store = load_password_store(&master_password)?;
+    let entry = find_entry(entry_id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+    begin_autofill("auto_fill_credentials_spotlight");
 
-    if let Some(entry) = store.entries.iter().find(|e| e.id == entry_id) {
-        // Hide Cocoon window
-        if let Some(window) = app_handle.get_webview_window("main") {
-            let _ = window.hide();
-        }
+    let press_enter = active_autofill_profile().press_enter;
+    finish_autofill(&app_handle, fill_credentials(&app_handle, &entry, press_enter))
+}
 
-        #[cfg(target_os = "macos")]
-        {
-            // Restore focus to target application
-            restore_target_focus()?;
-            std::thread::sleep(std::time::Duration::from_millis(200));
+// Types a harmless sentinel through the same focus+injection pipeline as autofill
+#[tauri::command]
+async fn test_autofill(text: String, app_handle: tauri::AppHandle) -> Result<(), String> {
+    if !cfg!(debug_assertions) && !load_settings()?.allow_autofill_test {
+        return Err("Autofill testing is disabled".to_string());
+    }
 
-            // Type credentials
-            simulate_typing_with_focus_restore(&entry.username)?;
-            simulate_tab()?;
-            std::thread::sleep(std::time::Duration::from_millis(100));
-            simulate_typing_with_focus_restore(&entry.password)?;
-        }
+    begin_autofill("test_autofill");
 
-        #[cfg(not(target_os = "macos"))]
-        {
-            std::thread::sleep(std::time::Duration::from_millis(500));
-            simulate_typing(&entry.username)?;
-            simulate_tab()?;
-            std::thread::sleep(std::time::Duration::from_millis(100));
-            simulate_typing(&entry.password)?;
-        }
+    let result = fill_single_field(&app_handle, AutofillStatus::TypingText, &text, 500);
+    finish_autofill(&app_handle, result)
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn AXIsProcessTrusted() -> bool;
+}
+
+#[derive(serde::Serialize)]
+struct InputCapability {
+    available: bool,
+    reason: Option<String>,
+}
+
+// Lets the UI explain why autofill is unavailable instead of failing silently
+#[tauri::command]
+async fn can_simulate_input() -> Result<InputCapability, String> {
+    #[cfg(target_os = "macos")]
+    let reason = if unsafe { AXIsProcessTrusted() } {
+        None
     } else {
-        return Err("Entry not found".to_string());
-    }
+        Some("Grant Accessibility permission to Cocoon to enable autofill".to_string())
+    };
 
-    Ok(())
+    #[cfg(target_os = "linux")]
+    let reason = unsafe {
+        let display = x11::xlib::XOpenDisplay(std::ptr::null());
+        if display.is_null() {
+            if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+                Some("Autofill needs XWayland; no X11 display is available in this Wayland session".to_string())
+            } else {
+                Some("No X11 display available for autofill".to_string())
+            }
+        } else {
+            x11::xlib::XCloseDisplay(display);
+            None
+        }
+    };
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    let reason: Option<String> = None;
+
+    Ok(InputCapability {
+        available: reason.is_none(),
+        reason,
+    })
 }
 
 // Add a command to focus the search input from the frontend
@@ -465,16 +1030,18 @@ async fn focus_search_input(app_handle: tauri::AppHandle) -> Result<(), String>
 fn simulate_tab() -> Result<(), String> {
     use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
 
+    ensure_autofill_active()?;
+
     let source = CGEventSource::new(CGEventSourceStateID::HIDSystemState)
         .map_err(|_| "Failed to create event source")?;
 
     // Tab key press
-    if let Ok(event) = CGEvent::new_keyboard_event(source.clone(), 48, true) {
+    if let Ok(event) = CGEvent::new_keyboard_event(source.clone(), KVK_TAB, true) {
         event.post(CGEventTapLocation::HID);
         std::thread::sleep(std::time::Duration::from_millis(50));
 
         // Tab key release
-        if let Ok(event_up) = CGEvent::new_keyboard_event(source, 48, false) {
+        if let Ok(event_up) = CGEvent::new_keyboard_event(source, KVK_TAB, false) {
             event_up.post(CGEventTapLocation::HID);
         }
     }
@@ -492,6 +1059,8 @@ fn simulate_typing(text: &str) -> Result<(), String> {
     let wide_text: Vec<u16> = OsStr::new(text).encode_wide().collect();
 
     for &ch in &wide_text {
+        ensure_autofill_active()?;
+
         let mut input = INPUT {
             type_: INPUT_KEYBOARD,
             u: unsafe { std::mem::zeroed() },
@@ -509,7 +1078,7 @@ fn simulate_typing(text: &str) -> Result<(), String> {
             }
         }
 
-        std::thread::sleep(std::time::Duration::from_millis(10));
+        std::thread::sleep(keystroke_delay(10));
     }
 
     Ok(())
@@ -519,6 +1088,8 @@ fn simulate_typing(text: &str) -> Result<(), String> {
 fn simulate_tab() -> Result<(), String> {
     use winapi::um::winuser::{SendInput, INPUT, INPUT_KEYBOARD, KEYEVENTF_KEYUP, VK_TAB};
 
+    ensure_autofill_active()?;
+
     let mut input_down = INPUT {
         type_: INPUT_KEYBOARD,
         u: unsafe { std::mem::zeroed() },
@@ -532,10 +1103,12 @@ fn simulate_tab() -> Result<(), String> {
     unsafe {
         // Tab key down
         input_down.u.ki_mut().wVk = VK_TAB as u16;
+        input_down.u.ki_mut().wScan = windows_scan_code(VK_TAB);
         input_down.u.ki_mut().dwFlags = 0;
 
         // Tab key up
         input_up.u.ki_mut().wVk = VK_TAB as u16;
+        input_up.u.ki_mut().wScan = windows_scan_code(VK_TAB);
         input_up.u.ki_mut().dwFlags = KEYEVENTF_KEYUP;
 
         if SendInput(1, &mut input_down, std::mem::size_of::<INPUT>() as i32) != 1 {
@@ -562,7 +1135,13 @@ fn simulate_typing(text: &str) -> Result<(), String> {
             return Err("Failed to open X11 display".to_string());
         }
 
+        let mut result = Ok(());
         for ch in text.chars() {
+            if let Err(e) = ensure_autofill_active() {
+                result = Err(e);
+                break;
+            }
+
             let keycode = ch as u32;
 
             // Key press
@@ -591,26 +1170,27 @@ fn simulate_typing(text: &str) -> Result<(), String> {
             );
 
             x11::xlib::XFlush(display);
-            std::thread::sleep(std::time::Duration::from_millis(10));
+            std::thread::sleep(keystroke_delay(10));
         }
 
         x11::xlib::XCloseDisplay(display);
+        result
     }
-
-    Ok(())
 }
 
 #[cfg(target_os = "linux")]
 fn simulate_tab() -> Result<(), String> {
     use std::ptr;
 
+    ensure_autofill_active()?;
+
     unsafe {
         let display = x11::xlib::XOpenDisplay(ptr::null());
         if display.is_null() {
             return Err("Failed to open X11 display".to_string());
         }
 
-        let tab_keycode = 23; // Tab key on most X11 systems
+        let tab_keycode = x11_keycode(display, x11::keysym::XK_Tab);
 
         // Key press
         let mut event: x11::xlib::XKeyEvent = std::mem::zeroed();
@@ -644,498 +1224,7708 @@ fn simulate_tab() -> Result<(), String> {
     Ok(())
 }
 
-fn get_data_file_path() -> Result<PathBuf, String> {
-    let app_data_dir = dirs::data_dir()
-        .ok_or("Could not find data directory")?
-        .join("cocoon-password-manager");
+fn env_dir(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
 
-    fs::create_dir_all(&app_data_dir)
-        .map_err(|e| format!("Failed to create data directory: {}", e))?;
+// Writable per-app dir provided by a packaging runtime, if we're running in one
+fn sandbox_data_dir() -> Option<PathBuf> {
+    // Snap confines writes to $SNAP_USER_DATA; the real ~/.local/share is off limits
+    if let Some(dir) = env_dir("SNAP_USER_DATA") {
+        return Some(dir);
+    }
+
+    // AppImages run from a read-only squashfs at $APPDIR, and some launchers point
+    // XDG_DATA_HOME into it, so skip anything under the mount
+    if env_dir("APPIMAGE").is_some() {
+        let appdir = env_dir("APPDIR");
+        let writable = |dir: &PathBuf| appdir.as_ref().map_or(true, |appdir| !dir.starts_with(appdir));
+        return dirs::data_dir()
+            .filter(writable)
+            .or_else(|| env_dir("HOME").map(|home| home.join(".local").join("share")))
+            .filter(writable);
+    }
 
-    Ok(app_data_dir.join("vault.cocoon"))
+    None
 }
 
-fn get_master_hash_path() -> Result<PathBuf, String> {
-    let app_data_dir = dirs::data_dir()
-        .ok_or("Could not find data directory")?
-        .join("cocoon-password-manager");
+// Minimal containers may have no XDG vars or HOME; never give up on a data dir
+fn resolve_base_data_dir() -> Result<PathBuf, String> {
+    sandbox_data_dir()
+        .or_else(dirs::data_dir)
+        .or_else(|| env_dir("XDG_DATA_HOME"))
+        .or_else(|| env_dir("HOME").map(|home| home.join(".local").join("share")))
+        .or_else(|| env_dir("COCOON_DATA_DIR"))
+        .or_else(|| std::env::current_dir().ok())
+        .ok_or_else(|| "Could not find data directory".to_string())
+}
 
-    Ok(app_data_dir.join("master.hash"))
+// Before Snap got its own branch, HOME-relative resolution put the data dir at
+// $SNAP_USER_DATA/.local/share
+fn legacy_snap_app_dir() -> Option<PathBuf> {
+    env_dir("SNAP_USER_DATA").map(|dir| dir.join(".local").join("share").join("cocoon-password-manager"))
 }
 
-// Security utility functions (keeping existing functions)
-fn generate_key_from_password(password: &str, salt: &[u8]) -> Result<Vec<u8>, String> {
-    let argon2 = Argon2::default();
-    let mut key = vec![0u8; 32]; // 256-bit key
+// Cocoon's own directory: settings and logs always live here
+fn get_app_data_dir() -> Result<PathBuf, String> {
+    let app_data_dir = resolve_base_data_dir()?.join("cocoon-password-manager");
+
+    if let Some(legacy_dir) = legacy_snap_app_dir() {
+        if legacy_dir.is_dir() && !app_data_dir.exists() {
+            // Same filesystem, so this is a cheap rename; if it fails keep
+            // using the old location rather than starting an empty vault
+            if let Err(e) = fs::rename(&legacy_dir, &app_data_dir) {
+                eprintln!("Failed to migrate data directory from {}: {}", legacy_dir.display(), e);
+                return Ok(legacy_dir);
+            }
+        }
+    }
 
-    argon2
-        .hash_password_into(password.as_bytes(), salt, &mut key)
-        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    fs::create_dir_all(&app_data_dir)
+        .map_err(|e| format!("Failed to create data directory: {}", e))?;
 
-    Ok(key)
+    Ok(app_data_dir)
 }
 
-fn encrypt_data(data: &str, key: &[u8]) -> Result<(String, String), String> {
-    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
-    let nonce = Aes256Gcm::generate_nonce(&mut AesOsRng);
+// Where vault.cocoon and master.hash live; may be redirected to a synced folder
+fn get_vault_dir() -> Result<PathBuf, String> {
+    match load_settings()?.vault_directory {
+        Some(dir) => {
+            let dir = PathBuf::from(dir);
+            if !dir.is_dir() {
+                return Err(format!(
+                    "Vault directory {} is not available",
+                    dir.display()
+                ));
+            }
+            Ok(dir)
+        }
+        None => get_app_data_dir(),
+    }
+}
 
-    let ciphertext = cipher
-        .encrypt(&nonce, data.as_bytes())
-        .map_err(|e| format!("Encryption failed: {}", e))?;
+fn get_data_file_path() -> Result<PathBuf, String> {
+    Ok(get_vault_dir()?.join("vault.cocoon"))
+}
 
-    Ok((
-        general_purpose::STANDARD.encode(&ciphertext),
-        general_purpose::STANDARD.encode(&nonce),
-    ))
+fn get_master_hash_path() -> Result<PathBuf, String> {
+    Ok(get_vault_dir()?.join("master.hash"))
 }
 
-fn decrypt_data(encrypted_data: &str, nonce: &str, key: &[u8]) -> Result<String, String> {
-    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+fn get_backup_dir() -> Result<PathBuf, String> {
+    let backup_dir = get_vault_dir()?.join("backups");
 
-    let ciphertext = general_purpose::STANDARD
-        .decode(encrypted_data)
-        .map_err(|e| format!("Failed to decode ciphertext: {}", e))?;
+    fs::create_dir_all(&backup_dir)
+        .map_err(|e| format!("Failed to create backup directory: {}", e))?;
 
-    let nonce_bytes = general_purpose::STANDARD
-        .decode(nonce)
-        .map_err(|e| format!("Failed to decode nonce: {}", e))?;
+    Ok(backup_dir)
+}
 
-    let nonce = Nonce::from_slice(&nonce_bytes);
+// Only bare file names inside backups/ are accepted
+fn get_backup_path(backup_filename: &str) -> Result<PathBuf, String> {
+    let is_bare_name = std::path::Path::new(backup_filename)
+        .file_name()
+        .is_some_and(|name| name == backup_filename);
+    if !is_bare_name {
+        return Err("Invalid backup file name".to_string());
+    }
 
-    let plaintext = cipher
-        .decrypt(nonce, ciphertext.as_ref())
-        .map_err(|e| format!("Decryption failed: {}", e))?;
+    let path = get_backup_dir()?.join(backup_filename);
+    if !path.exists() {
+        return Err(format!("Backup {} not found", backup_filename));
+    }
+    Ok(path)
+}
 
-    String::from_utf8(plaintext).map_err(|e| format!("Invalid UTF-8 in decrypted data: {}", e))
+fn get_settings_path() -> Result<PathBuf, String> {
+    Ok(get_app_data_dir()?.join("settings.json"))
 }
 
-// Authentication functions (keeping existing functions)
-#[tauri::command]
-async fn setup_master_password(password: String) -> Result<(), String> {
-    if password.len() < 8 {
-        return Err("Master password must be at least 8 characters long".to_string());
+// User preferences, stored as plain JSON next to the vault (no secrets here)
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(default)]
+struct AppSettings {
+    autostart: bool,
+    allow_autofill_test: bool,
+    log_level: Option<String>,
+    vault_directory: Option<String>,
+    password_max_age_days: Option<u32>,
+    // Keeps entry titles in a plain, unencrypted file so they can be searched
+    // before unlock; anyone who can read the app data dir can read them
+    locked_preview: bool,
+    show_tray: bool,
+    panic_shortcut: Option<String>,
+    keyfile_path: Option<String>,
+    // Entry id -> shortcut that autofills it directly
+    entry_hotkeys: HashMap<u32, String>,
+    searchable_fields: std::collections::HashSet<FieldKind>,
+    // Off by default: anything that contacts entry URLs is opt-in
+    allow_network_checks: bool,
+    compress_vault: bool,
+    // Open the panel on the monitor under the mouse rather than the primary one
+    center_on_active_monitor: bool,
+    // Strip stray leading/trailing whitespace from values as they're typed
+    trim_on_fill: bool,
+    autofill_profiles: HashMap<String, AutofillProfile>,
+    active_autofill_profile: Option<String>,
+    // Wipe anything Cocoon copied once the panel goes away
+    clear_clipboard_on_hide: bool,
+    // Grace period after losing focus before the panel hides
+    blur_hide_delay_ms: u64,
+    // Lock after this many minutes without activity; None never locks
+    idle_timeout_mins: Option<u32>,
+    hide_on_blur: bool,
+    // Browser extensions allowed to request credentials: Chrome origins
+    // ("chrome-extension://<id>/") and Firefox extension ids
+    native_messaging_origins: Vec<String>,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            autostart: false,
+            allow_autofill_test: false,
+            log_level: None,
+            vault_directory: None,
+            password_max_age_days: None,
+            locked_preview: false,
+            show_tray: true,
+            panic_shortcut: Some(DEFAULT_PANIC_SHORTCUT.to_string()),
+            keyfile_path: None,
+            entry_hotkeys: HashMap::new(),
+            searchable_fields: [FieldKind::Title, FieldKind::Username, FieldKind::Url]
+                .into_iter()
+                .collect(),
+            allow_network_checks: false,
+            compress_vault: true,
+            center_on_active_monitor: true,
+            trim_on_fill: true,
+            autofill_profiles: HashMap::new(),
+            active_autofill_profile: None,
+            clear_clipboard_on_hide: true,
+            blur_hide_delay_ms: DEFAULT_BLUR_HIDE_DELAY_MS,
+            idle_timeout_mins: None,
+            hide_on_blur: true,
+            native_messaging_origins: Vec::new(),
+        }
     }
+}
 
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2
-        .hash_password(password.as_bytes(), &salt)
-        .map_err(|e| format!("Failed to hash password: {}", e))?;
+fn load_settings() -> Result<AppSettings, String> {
+    let settings_path = get_settings_path()?;
 
-    let hash_path = get_master_hash_path()?;
-    fs::write(&hash_path, password_hash.to_string())
-        .map_err(|e| format!("Failed to save master password hash: {}", e))?;
+    if !settings_path.exists() {
+        return Ok(AppSettings::default());
+    }
 
-    // Initialize empty encrypted store
-    let empty_store = PasswordStore::default();
-    let store_json = serde_json::to_string(&empty_store)
-        .map_err(|e| format!("Failed to serialize empty store: {}", e))?;
+    let content = fs::read_to_string(&settings_path)
+        .map_err(|e| format!("Failed to read settings: {}", e))?;
 
-    let salt_bytes = salt.as_str().as_bytes();
-    let key = generate_key_from_password(&password, salt_bytes)?;
-    let (encrypted_data, nonce) = encrypt_data(&store_json, &key)?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse settings: {}", e))
+}
 
-    let encrypted_store = EncryptedPasswordStore {
-        encrypted_data,
-        nonce,
-        salt: general_purpose::STANDARD.encode(salt_bytes),
-        iterations: 100_000,
-        version: 1,
-    };
+fn save_settings(settings: &AppSettings) -> Result<(), String> {
+    let settings_path = get_settings_path()?;
+    let content = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
 
-    save_encrypted_store(&encrypted_store)?;
+    fs::write(&settings_path, content).map_err(|e| format!("Failed to write settings: {}", e))
+}
+
+// Bring the OS launch-at-login registration in line with the requested state
+fn apply_autostart(app_handle: &tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    let autostart_manager = app_handle.autolaunch();
+    let is_enabled = autostart_manager.is_enabled().unwrap_or(false);
+
+    if enabled && !is_enabled {
+        autostart_manager
+            .enable()
+            .map_err(|e| format!("Failed to enable autostart: {}", e))?;
+    } else if !enabled && is_enabled {
+        autostart_manager
+            .disable()
+            .map_err(|e| format!("Failed to disable autostart: {}", e))?;
+    }
 
     Ok(())
 }
 
 #[tauri::command]
-fn verify_master_password(password: &str) -> Result<Vec<u8>, String> {
-    let hash_path = get_master_hash_path()?;
-    if !hash_path.exists() {
-        return Err("Master password not set".to_string());
-    }
+async fn get_autostart() -> Result<bool, String> {
+    Ok(load_settings()?.autostart)
+}
 
-    let stored_hash = fs::read_to_string(&hash_path)
-        .map_err(|e| format!("Failed to read master password hash: {}", e))?;
+#[tauri::command]
+async fn set_autostart(enabled: bool, app_handle: tauri::AppHandle) -> Result<(), String> {
+    apply_autostart(&app_handle, enabled)?;
 
-    let parsed_hash = PasswordHash::new(&stored_hash)
-        .map_err(|e| format!("Failed to parse password hash: {}", e))?;
+    let mut settings = load_settings()?;
+    settings.autostart = enabled;
+    save_settings(&settings)
+}
 
-    let argon2 = Argon2::default();
-    argon2
-        .verify_password(password.as_bytes(), &parsed_hash)
-        .map_err(|_| "Invalid master password".to_string())?;
+// Rename, or copy+delete when the target is on another filesystem
+fn move_file(from: &std::path::Path, to: &std::path::Path) -> Result<(), String> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
 
-    // Generate and return the key
-    let salt = parsed_hash.salt.unwrap().as_str().as_bytes();
-    generate_key_from_password(password, salt)
+    fs::copy(from, to).map_err(|e| format!("Failed to copy {}: {}", from.display(), e))?;
+    fs::remove_file(from).map_err(|e| format!("Failed to remove {}: {}", from.display(), e))
 }
 
+// Resolved location including Snap/AppImage and custom-directory redirects
 #[tauri::command]
-async fn has_master_password() -> Result<bool, String> {
-    let hash_path = get_master_hash_path()?;
-    Ok(hash_path.exists())
+async fn get_vault_path() -> Result<String, String> {
+    Ok(get_data_file_path()?.to_string_lossy().to_string())
 }
 
-// Encrypted store functions (keeping existing functions)
-fn save_encrypted_store(store: &EncryptedPasswordStore) -> Result<(), String> {
-    let file_path = get_data_file_path()?;
-    let content = serde_json::to_string_pretty(store)
-        .map_err(|e| format!("Failed to serialize encrypted store: {}", e))?;
+// Point the vault at another folder (e.g. Dropbox); `None` restores the default
+#[tauri::command]
+async fn set_vault_directory(path: Option<String>, move_existing: bool) -> Result<(), String> {
+    // Not get_vault_dir(): resetting has to work when the configured folder
+    // is gone (unmounted drive, removed sync client)
+    let current_dir = match load_settings()?.vault_directory {
+        Some(dir) => PathBuf::from(dir),
+        None => get_app_data_dir()?,
+    };
+    let current_dir = current_dir.canonicalize().unwrap_or(current_dir);
 
-    fs::write(&file_path, content).map_err(|e| format!("Failed to write encrypted store: {}", e))
-}
+    let new_dir = match &path {
+        Some(path) => {
+            let dir = PathBuf::from(path);
+            if dir.exists() && !dir.is_dir() {
+                return Err(format!("{} is not a directory", dir.display()));
+            }
+            fs::create_dir_all(&dir)
+                .map_err(|e| format!("Failed to create vault directory: {}", e))?;
 
-fn load_encrypted_store() -> Result<EncryptedPasswordStore, String> {
-    let file_path = get_data_file_path()?;
+            let probe = dir.join(".cocoon-write-test");
+            fs::write(&probe, b"ok")
+                .map_err(|e| format!("Vault directory is not writable: {}", e))?;
+            let _ = fs::remove_file(&probe);
 
-    if !file_path.exists() {
-        return Err("Encrypted store not found".to_string());
+            dir.canonicalize()
+                .map_err(|e| format!("Failed to resolve vault directory: {}", e))?
+        }
+        None => get_app_data_dir()?
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve vault directory: {}", e))?,
+    };
+    let path = path.map(|_| new_dir.to_string_lossy().to_string());
+
+    if move_existing && new_dir != current_dir {
+        let files = ["vault.cocoon", "master.hash"];
+
+        if files.iter().any(|f| new_dir.join(f).exists()) {
+            return Err("Target directory already contains a vault".to_string());
+        }
+
+        // Put back whatever already moved so the vault is never split
+        // between the two folders
+        let mut moved = Vec::new();
+        for file in files {
+            let from = current_dir.join(file);
+            if !from.exists() {
+                continue;
+            }
+            let to = new_dir.join(file);
+            if let Err(e) = move_file(&from, &to) {
+                for (from, to) in moved.iter().rev() {
+                    if let Err(e) = move_file(to, from) {
+                        tracing::error!("Failed to move {} back: {}", to.display(), e);
+                    }
+                }
+                return Err(e);
+            }
+            moved.push((from, to));
+        }
     }
 
-    let content = fs::read_to_string(&file_path)
-        .map_err(|e| format!("Failed to read encrypted store: {}", e))?;
+    let mut settings = load_settings()?;
+    settings.vault_directory = path;
+    save_settings(&settings)?;
 
-    serde_json::from_str(&content).map_err(|e| format!("Failed to parse encrypted store: {}", e))
+    // Whatever was cached belongs to the old location
+    UNLOCKED_STORE.lock().unwrap().take();
+    VAULT_FINGERPRINT.lock().unwrap().take();
+    Ok(())
 }
 
-fn load_password_store(master_password: &str) -> Result<PasswordStore, String> {
-    let key = verify_master_password(master_password)?;
-    let encrypted_store = load_encrypted_store()?;
-    let decrypted_data = decrypt_data(
-        &encrypted_store.encrypted_data,
-        &encrypted_store.nonce,
-        &key,
-    )?;
+fn get_log_dir() -> Result<PathBuf, String> {
+    let log_dir = get_app_data_dir()?.join("logs");
 
-    serde_json::from_str(&decrypted_data)
-        .map_err(|e| format!("Failed to parse decrypted store: {}", e))
+    fs::create_dir_all(&log_dir).map_err(|e| format!("Failed to create log directory: {}", e))?;
+
+    Ok(log_dir)
 }
 
-fn save_password_store(store: &PasswordStore, master_password: &str) -> Result<(), String> {
-    let key = verify_master_password(master_password)?;
-    let store_json =
-        serde_json::to_string(store).map_err(|e| format!("Failed to serialize store: {}", e))?;
+type LogLevelHandle =
+    tracing_subscriber::reload::Handle<tracing_subscriber::filter::LevelFilter, tracing_subscriber::Registry>;
 
-    let (encrypted_data, nonce) = encrypt_data(&store_json, &key)?;
+lazy_static::lazy_static! {
+    static ref LOG_LEVEL_HANDLE: Mutex<Option<LogLevelHandle>> = Mutex::new(None);
+}
 
-    // Load existing encrypted store to preserve salt and other metadata
-    let mut encrypted_store = load_encrypted_store().unwrap_or_else(|_| {
-        // Create new encrypted store if none exists
-        EncryptedPasswordStore {
-            encrypted_data: String::new(),
-            nonce: String::new(),
-            salt: String::new(),
-            iterations: 100_000,
-            version: 1,
-        }
-    });
+const INPUT_BACKEND: &str = if cfg!(target_os = "macos") {
+    "core-graphics"
+} else if cfg!(target_os = "windows") {
+    "sendinput"
+} else {
+    "x11"
+};
 
-    encrypted_store.encrypted_data = encrypted_data;
-    encrypted_store.nonce = nonce;
+static UNLOCK_SUCCESSES: AtomicU32 = AtomicU32::new(0);
+static UNLOCK_FAILURES: AtomicU32 = AtomicU32::new(0);
 
-    save_encrypted_store(&encrypted_store)
+// Rewritten down to the newest UNLOCK_AUDIT_KEEP lines once the file passes
+// UNLOCK_AUDIT_MAX_BYTES (roughly UNLOCK_AUDIT_MAX lines)
+const UNLOCK_AUDIT_MAX: usize = 1000;
+const UNLOCK_AUDIT_KEEP: usize = 500;
+const UNLOCK_AUDIT_MAX_BYTES: u64 = 96 * 1024;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct UnlockAttempt {
+    timestamp: String,
+    success: bool,
+    method: String,
 }
 
-fn calculate_password_strength(password: &str) -> u8 {
-    let mut score = 0u8;
+fn get_unlock_audit_path() -> Result<PathBuf, String> {
+    Ok(get_app_data_dir()?.join("unlock-audit.jsonl"))
+}
 
-    // Length scoring
-    if password.len() >= 8 {
-        score += 20;
-    }
-    if password.len() >= 12 {
-        score += 15;
-    }
-    if password.len() >= 16 {
-        score += 10;
-    }
+// One JSON object per line; never includes the password or anything derived from it
+fn record_unlock_attempt(success: bool, method: &str) {
+    use std::io::Write;
 
-    // Character variety
-    if password.chars().any(|c| c.is_ascii_lowercase()) {
-        score += 5;
-    }
-    if password.chars().any(|c| c.is_ascii_uppercase()) {
-        score += 5;
-    }
-    if password.chars().any(|c| c.is_ascii_digit()) {
-        score += 5;
-    }
-    if password
-        .chars()
-        .any(|c| "!@#$%^&*()_+-=[]{}|;:,.<>?".contains(c))
-    {
-        score += 10;
-    }
+    let attempt = UnlockAttempt {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        success,
+        method: method.to_string(),
+    };
+    let result = get_unlock_audit_path().and_then(|path| {
+        let line = serde_json::to_string(&attempt).map_err(|e| e.to_string())?;
+        let size = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| {
+                writeln!(file, "{}", line)?;
+                file.metadata()
+            })
+            .map_err(|e| e.to_string())?
+            .len();
+        if size <= UNLOCK_AUDIT_MAX_BYTES {
+            return Ok(());
+        }
 
-    // Complexity bonus
-    let unique_chars = password
-        .chars()
-        .collect::<std::collections::HashSet<_>>()
-        .len();
-    if unique_chars > password.len() / 2 {
-        score += 10;
+        let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let lines: Vec<&str> = content.lines().collect();
+        let kept = lines[lines.len().saturating_sub(UNLOCK_AUDIT_KEEP)..].join("\n") + "\n";
+        write_atomically(&path, kept.as_bytes())
+    });
+
+    if let Err(e) = result {
+        tracing::warn!("Failed to record unlock attempt: {}", e);
     }
+}
 
-    // Penalty for common patterns
-    if password.to_lowercase().contains("password")
-        || password.to_lowercase().contains("123456")
-        || password
-            .chars()
-            .collect::<Vec<_>>()
-            .windows(3)
-            .any(|w| w[0] == w[1] && w[1] == w[2])
-    {
-        score = score.saturating_sub(20);
+// Newest first
+#[tauri::command]
+async fn get_unlock_audit(limit: Option<usize>) -> Result<Vec<UnlockAttempt>, String> {
+    let path = get_unlock_audit_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
     }
 
-    score.min(100)
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read unlock audit log: {}", e))?;
+    Ok(content
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .take(limit.unwrap_or(UNLOCK_AUDIT_MAX))
+        .collect())
 }
 
 #[tauri::command]
-async fn auto_fill_credentials_spotlight_with_login(
-    entry_id: u32,
-    master_password: String,
-    press_enter: bool,
-    app_handle: tauri::AppHandle,
-) -> Result<(), String> {
-    let store = load_password_store(&master_password)?;
+async fn clear_unlock_audit(master_password: String) -> Result<(), String> {
+    derive_master_key(&master_password)?;
+
+    let path = get_unlock_audit_path()?;
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| format!("Failed to clear unlock audit log: {}", e))?;
+    }
+    tracing::info!("Unlock audit log cleared");
+    Ok(())
+}
 
-    if let Some(entry) = store.entries.iter().find(|e| e.id == entry_id) {
-        // Hide Cocoon window
-        if let Some(window) = app_handle.get_webview_window("main") {
-            let _ = window.hide();
+// Field names whose values must never reach the log file, whatever the call site
+fn is_sensitive_log_field(name: &str) -> bool {
+    const SENSITIVE: [&str; 8] = [
+        "password", "secret", "key", "token", "pin", "candidate", "text", "otp",
+    ];
+    let name = name.to_lowercase();
+    SENSITIVE.iter().any(|s| name.contains(s))
+}
+
+lazy_static::lazy_static! {
+    // `password: hunter2`, `token=abc` and the like inside free-form messages
+    static ref SENSITIVE_ASSIGNMENT: regex::Regex =
+        regex::Regex::new(r"(?i)\b(password|passphrase|secret|key|token|pin|otp)(\s*[:=]\s*)\S+").unwrap();
+    // Long base64/hex runs look like keys, ciphertext or tokens
+    static ref OPAQUE_BLOB: regex::Regex = regex::Regex::new(r"[A-Za-z0-9+/_-]{32,}={0,2}").unwrap();
+}
+
+// Field redaction can't see into the message text, so formatted messages are
+// scrubbed by pattern as well
+fn scrub_log_message(message: &str) -> String {
+    let message = SENSITIVE_ASSIGNMENT.replace_all(message, "$1$2[redacted]");
+    OPAQUE_BLOB.replace_all(&message, "[redacted]").into_owned()
+}
+
+fn parse_log_level(level: &str) -> Result<tracing_subscriber::filter::LevelFilter, String> {
+    level
+        .parse()
+        .map_err(|_| format!("Unknown log level '{}'", level))
+}
+
+fn init_logging() -> Result<(), String> {
+    use tracing_subscriber::field::MakeExt;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    let level = load_settings()
+        .ok()
+        .and_then(|s| s.log_level)
+        .and_then(|l| parse_log_level(&l).ok())
+        .unwrap_or(tracing_subscriber::filter::LevelFilter::INFO);
+
+    let appender = tracing_appender::rolling::RollingFileAppender::builder()
+        .rotation(tracing_appender::rolling::Rotation::DAILY)
+        .filename_prefix("cocoon")
+        .filename_suffix("log")
+        .max_log_files(7)
+        .build(get_log_dir()?)
+        .map_err(|e| format!("Failed to create log file: {}", e))?;
+
+    // Redaction layer: sensitive fields are replaced before formatting
+    let redacting_fields = tracing_subscriber::fmt::format::debug_fn(|writer, field, value| {
+        if field.name() == "message" {
+            write!(writer, "{}", scrub_log_message(&format!("{:?}", value)))
+        } else if is_sensitive_log_field(field.name()) {
+            write!(writer, "{}=[redacted]", field)
+        } else {
+            write!(writer, "{}={:?}", field, value)
         }
+    })
+    .delimited(" ");
 
-        #[cfg(target_os = "macos")]
-        {
-            // Restore focus to target application
-            restore_target_focus()?;
-            std::thread::sleep(std::time::Duration::from_millis(200));
+    let (level_layer, level_handle) = tracing_subscriber::reload::Layer::new(level);
+
+    tracing_subscriber::registry()
+        .with(level_layer)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .fmt_fields(redacting_fields)
+                .with_writer(appender),
+        )
+        .try_init()
+        .map_err(|e| format!("Failed to initialize logging: {}", e))?;
+
+    *LOG_LEVEL_HANDLE.lock().unwrap() = Some(level_handle);
+
+    Ok(())
+}
 
-            // Type credentials
-            simulate_typing_with_focus_restore(&entry.username)?;
-            simulate_tab()?;
-            std::thread::sleep(std::time::Duration::from_millis(100));
-            simulate_typing_with_focus_restore(&entry.password)?;
-            
-            // Optionally press Enter to login
-            if press_enter {
-                std::thread::sleep(std::time::Duration::from_millis(200));
-                simulate_enter()?;
+// Logs rotate daily as `cocoon.<date>.log`; this is the file being written now
+#[tauri::command]
+async fn get_log_path() -> Result<String, String> {
+    let log_dir = get_log_dir()?;
+    let newest = fs::read_dir(&log_dir)
+        .map_err(|e| format!("Failed to read log directory: {}", e))?
+        .filter_map(Result::ok)
+        .filter(|file| {
+            let name = file.file_name().to_string_lossy().to_string();
+            name.starts_with("cocoon.") && name.ends_with(".log")
+        })
+        .max_by_key(|file| file.metadata().and_then(|m| m.modified()).ok())
+        .map(|file| file.path());
+
+    // Nothing logged yet today: the appender names files by UTC date
+    let path = newest
+        .unwrap_or_else(|| log_dir.join(format!("cocoon.{}.log", chrono::Utc::now().format("%Y-%m-%d"))));
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+async fn set_log_level(level: String) -> Result<(), String> {
+    let level_filter = parse_log_level(&level)?;
+
+    if let Some(handle) = LOG_LEVEL_HANDLE.lock().unwrap().as_ref() {
+        handle
+            .reload(level_filter)
+            .map_err(|e| format!("Failed to change log level: {}", e))?;
+    }
+
+    let mut settings = load_settings()?;
+    settings.log_level = Some(level);
+    save_settings(&settings)
+}
+
+// Security utility functions (keeping existing functions)
+fn generate_key_from_password(password: &str, salt: &[u8]) -> Result<Vec<u8>, String> {
+    generate_key_with_params(password, salt, argon2::Params::default())
+}
+
+fn kdf_params_header(params: &argon2::Params) -> String {
+    format!("m={},t={},p={}", params.m_cost(), params.t_cost(), params.p_cost())
+}
+
+fn header_kdf_params(store: &EncryptedPasswordStore) -> Result<argon2::Params, String> {
+    let Some(header) = store.kdf_params.as_deref() else {
+        return Ok(argon2::Params::default());
+    };
+
+    let mut cost = (None, None, None);
+    for part in header.split(',') {
+        let (name, value) = part
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid KDF parameters '{}'", header))?;
+        let value: u32 = value
+            .parse()
+            .map_err(|_| format!("Invalid KDF parameters '{}'", header))?;
+        match name {
+            "m" => cost.0 = Some(value),
+            "t" => cost.1 = Some(value),
+            "p" => cost.2 = Some(value),
+            _ => return Err(format!("Invalid KDF parameters '{}'", header)),
+        }
+    }
+
+    let (Some(m_cost), Some(t_cost), Some(p_cost)) = cost else {
+        return Err(format!("Invalid KDF parameters '{}'", header));
+    };
+    argon2::Params::new(m_cost, t_cost, p_cost, Some(32)).map_err(|e| format!("Invalid KDF parameters: {}", e))
+}
+
+// Known-answer test for the default-parameter derivation that new vaults and
+// backups rely on. The expected key was computed independently (Argon2id v1.3,
+// m=19456 KiB, t=2, p=1, 32-byte output); if a dependency bump changes
+// Argon2::default() or the implementation, this fails instead of every vault.
+const KDF_SELF_TEST_PASSWORD: &str = "correct horse battery staple";
+const KDF_SELF_TEST_SALT: &[u8] = b"cocoon-kdf-self-test";
+const KDF_SELF_TEST_EXPECTED: &str =
+    "e080b5392e6cc430c9c23f0a137c92ffd64c27b27a54676fd23c4ae92a872ec5";
+
+#[tauri::command]
+fn self_test() -> Result<(), String> {
+    let key = zeroize::Zeroizing::new(generate_key_from_password(
+        KDF_SELF_TEST_PASSWORD,
+        KDF_SELF_TEST_SALT,
+    )?);
+    let key_hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+
+    if key_hex != KDF_SELF_TEST_EXPECTED {
+        let error = "KDF self-test failed: key derivation no longer matches this vault format. \
+                     Existing vaults cannot be opened by this build."
+            .to_string();
+        *SELF_TEST_FAILURE.lock().unwrap() = Some(error.clone());
+        return Err(error);
+    }
+    Ok(())
+}
+
+lazy_static::lazy_static! {
+    // Set by a failed startup self-test; unlocking is refused rather than
+    // deriving keys that can't match the vault (and rekeying under them)
+    static ref SELF_TEST_FAILURE: Mutex<Option<String>> = Mutex::new(None);
+}
+
+lazy_static::lazy_static! {
+    // Measured derivation time for (m_cost, t_cost, p_cost); a rekey to new
+    // parameters misses the cache and is measured again
+    static ref KDF_COST_ESTIMATE: Mutex<Option<((u32, u32, u32), u64)>> = Mutex::new(None);
+}
+
+fn current_kdf_params() -> Result<argon2::Params, String> {
+    let hash_path = get_master_hash_path()?;
+    if !hash_path.exists() {
+        return Ok(argon2::Params::default());
+    }
+
+    let stored_hash = fs::read_to_string(&hash_path)
+        .map_err(|e| format!("Failed to read master password hash: {}", e))?;
+    let parsed_hash = PasswordHash::new(stored_hash.trim())
+        .map_err(|e| format!("Failed to parse password hash: {}", e))?;
+    argon2::Params::try_from(&parsed_hash).map_err(|e| format!("Failed to read KDF parameters: {}", e))
+}
+
+// Expected unlock time in ms, so the UI can decide whether a spinner is worth showing
+#[tauri::command]
+async fn kdf_cost_estimate() -> Result<u64, String> {
+    let params = current_kdf_params()?;
+    let cache_key = (params.m_cost(), params.t_cost(), params.p_cost());
+
+    if let Some((key, estimate_ms)) = *KDF_COST_ESTIMATE.lock().unwrap() {
+        if key == cache_key {
+            return Ok(estimate_ms);
+        }
+    }
+
+    let started = std::time::Instant::now();
+    generate_key_with_params("cocoon-kdf-estimate", KDF_SELF_TEST_SALT, params)?;
+    let estimate_ms = started.elapsed().as_millis() as u64;
+
+    *KDF_COST_ESTIMATE.lock().unwrap() = Some((cache_key, estimate_ms));
+    tracing::debug!(estimate_ms, "Measured KDF cost");
+    Ok(estimate_ms)
+}
+
+// Vault keys follow the Argon2 cost recorded in master.hash, so a vault
+// rekeyed with stronger parameters keeps opening
+fn generate_key_with_params(
+    password: &str,
+    salt: &[u8],
+    params: argon2::Params,
+) -> Result<Vec<u8>, String> {
+    let argon2 = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+    let mut key = vec![0u8; 32]; // 256-bit key
+
+    argon2
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+
+    Ok(key)
+}
+
+fn encrypt_data(data: &str, key: &[u8]) -> Result<(String, String), String> {
+    encrypt_bytes(data.as_bytes(), key)
+}
+
+fn encrypt_bytes(data: &[u8], key: &[u8]) -> Result<(String, String), String> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut AesOsRng);
+
+    let ciphertext = cipher
+        .encrypt(&nonce, data)
+        .map_err(|e| format!("Encryption failed: {}", e))?;
+
+    Ok((
+        general_purpose::STANDARD.encode(&ciphertext),
+        general_purpose::STANDARD.encode(&nonce),
+    ))
+}
+
+fn decrypt_data(encrypted_data: &str, nonce: &str, key: &[u8]) -> Result<String, String> {
+    let plaintext = decrypt_bytes(encrypted_data, nonce, key)?;
+
+    String::from_utf8(plaintext).map_err(|e| format!("Invalid UTF-8 in decrypted data: {}", e))
+}
+
+fn decrypt_bytes(encrypted_data: &str, nonce: &str, key: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+
+    let ciphertext = general_purpose::STANDARD
+        .decode(encrypted_data)
+        .map_err(|e| format!("Failed to decode ciphertext: {}", e))?;
+
+    let nonce_bytes = general_purpose::STANDARD
+        .decode(nonce)
+        .map_err(|e| format!("Failed to decode nonce: {}", e))?;
+
+    let nonce = Nonce::from_slice(&nonce_bytes);
+
+    cipher
+        .decrypt(nonce, ciphertext.as_ref())
+        .map_err(|e| format!("Decryption failed: {}", e))
+}
+
+// Encrypt serialized store JSON, gzipping first when enabled in settings.
+// Returns (ciphertext, nonce, compressed) for the vault header.
+fn seal_store_json(store_json: &str, key: &[u8]) -> Result<(String, String, bool), String> {
+    let compress = load_settings().map(|s| s.compress_vault).unwrap_or(true);
+    seal_with_compression(store_json, key, compress)
+}
+
+fn seal_with_compression(store_json: &str, key: &[u8], compress: bool) -> Result<(String, String, bool), String> {
+    use std::io::Write;
+
+    if !compress {
+        let (encrypted_data, nonce) = encrypt_data(store_json, key)?;
+        return Ok((encrypted_data, nonce, false));
+    }
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(store_json.as_bytes())
+        .and_then(|_| encoder.finish())
+        .map_err(|e| format!("Failed to compress store: {}", e))
+        .and_then(|compressed| encrypt_bytes(&compressed, key))
+        .map(|(encrypted_data, nonce)| (encrypted_data, nonce, true))
+}
+
+fn unseal_store_json(plaintext: Vec<u8>, compressed: bool) -> Result<String, String> {
+    use std::io::Read;
+
+    if !compressed {
+        return String::from_utf8(plaintext)
+            .map_err(|e| format!("Invalid UTF-8 in decrypted data: {}", e));
+    }
+
+    let mut store_json = String::new();
+    flate2::read::GzDecoder::new(plaintext.as_slice())
+        .read_to_string(&mut store_json)
+        .map_err(|e| format!("Failed to decompress store: {}", e))?;
+    Ok(store_json)
+}
+
+// Authentication functions (keeping existing functions)
+const KEYFILE_REQUIRED_ERROR: &str = "This vault requires a keyfile to unlock";
+const KEYFILE_MISMATCH_ERROR: &str = "Keyfile does not match this vault";
+
+fn keyfile_digest(path: &str) -> Result<[u8; 32], String> {
+    use sha2::{Digest, Sha256};
+
+    let contents =
+        fs::read(path).map_err(|e| format!("Keyfile could not be read ({}): {}", path, e))?;
+    Ok(Sha256::digest(&contents).into())
+}
+
+// Kept in the vault header so a wrong keyfile isn't reported as a wrong password
+fn keyfile_check(digest: &[u8; 32]) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    hasher.update(b"cocoon-keyfile-check");
+    hasher.update(digest);
+    general_purpose::STANDARD.encode(hasher.finalize())
+}
+
+fn combine_with_keyfile(password_key: &[u8], digest: &[u8; 32]) -> Result<Vec<u8>, String> {
+    let hkdf = hkdf::Hkdf::<sha2::Sha256>::new(Some(digest), password_key);
+    let mut key = vec![0u8; 32];
+    hkdf.expand(b"cocoon-vault-key", &mut key)
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+// Fold the configured keyfile into a password-derived key when the vault asks for one
+fn apply_keyfile(password_key: Vec<u8>) -> Result<Vec<u8>, String> {
+    let expected_check = load_encrypted_store().ok().and_then(|s| s.keyfile_check);
+    apply_keyfile_check(password_key, expected_check.as_deref())
+}
+
+fn apply_keyfile_check(password_key: Vec<u8>, expected_check: Option<&str>) -> Result<Vec<u8>, String> {
+    let password_key = zeroize::Zeroizing::new(password_key);
+    let expected_check = match expected_check {
+        Some(check) => check,
+        None => return Ok(password_key.to_vec()),
+    };
+
+    let keyfile_path = load_settings()?
+        .keyfile_path
+        .ok_or_else(|| KEYFILE_REQUIRED_ERROR.to_string())?;
+    let digest = keyfile_digest(&keyfile_path)?;
+    if keyfile_check(&digest) != expected_check {
+        return Err(KEYFILE_MISMATCH_ERROR.to_string());
+    }
+
+    combine_with_keyfile(&password_key, &digest)
+}
+
+// One password per line, lowercase, most common first; bundled so the check
+// works offline. Regenerate from the SecLists top-10k list with
+// scripts/update-common-passwords.sh.
+const COMMON_PASSWORDS: &str = include_str!("../resources/common-passwords.txt");
+
+lazy_static::lazy_static! {
+    static ref COMMON_PASSWORD_SET: std::collections::HashSet<&'static str> = COMMON_PASSWORDS
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+}
+
+#[derive(serde::Serialize)]
+struct MasterPasswordCheck {
+    blocklisted: bool,
+    strength: u8,
+}
+
+// Lets the setup screen warn or refuse before calling setup_master_password
+#[tauri::command]
+async fn validate_master_password_candidate(candidate: String) -> Result<MasterPasswordCheck, String> {
+    let blocklisted = COMMON_PASSWORD_SET.contains(candidate.as_str())
+        || COMMON_PASSWORD_SET.contains(candidate.to_lowercase().as_str());
+
+    Ok(MasterPasswordCheck {
+        blocklisted,
+        strength: calculate_password_strength(&candidate),
+    })
+}
+
+#[tauri::command]
+async fn setup_master_password(password: String) -> Result<(), String> {
+    create_vault(&password, None)
+}
+
+#[tauri::command]
+async fn setup_master_password_with_keyfile(
+    password: String,
+    keyfile_path: String,
+) -> Result<(), String> {
+    create_vault(&password, Some(&keyfile_path))
+}
+
+fn create_vault(password: &str, keyfile_path: Option<&str>) -> Result<(), String> {
+    if password.len() < 8 {
+        return Err("Master password must be at least 8 characters long".to_string());
+    }
+
+    let salt = SaltString::generate(&mut OsRng);
+    let argon2 = Argon2::default();
+    let password_hash = argon2
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| format!("Failed to hash password: {}", e))?;
+
+    // Read the keyfile before touching disk so a bad path leaves no half-made vault
+    let digest = keyfile_path.map(keyfile_digest).transpose()?;
+
+    let hash_path = get_master_hash_path()?;
+    fs::write(&hash_path, password_hash.to_string())
+        .map_err(|e| format!("Failed to save master password hash: {}", e))?;
+
+    // Initialize empty encrypted store
+    let empty_store = PasswordStore::default();
+    let store_json = serde_json::to_string(&empty_store)
+        .map_err(|e| format!("Failed to serialize empty store: {}", e))?;
+
+    let salt_bytes = salt.as_str().as_bytes();
+    let mut key = generate_key_from_password(password, salt_bytes)?;
+
+    let keyfile_check = match &digest {
+        Some(digest) => {
+            key = combine_with_keyfile(&key, digest)?;
+            Some(keyfile_check(digest))
+        }
+        None => None,
+    };
+    let (encrypted_data, nonce) = encrypt_data(&store_json, &key)?;
+
+    let encrypted_store = EncryptedPasswordStore {
+        encrypted_data,
+        nonce,
+        salt: general_purpose::STANDARD.encode(salt_bytes),
+        iterations: 100_000,
+        version: VAULT_FORMAT_VERSION,
+        keyfile_check,
+        revision: 0,
+        compressed: false,
+        kdf_params: Some(kdf_params_header(&argon2::Params::default())),
+    };
+
+    save_encrypted_store(&encrypted_store)?;
+
+    let mut settings = load_settings()?;
+    settings.keyfile_path = keyfile_path.map(str::to_string);
+    save_settings(&settings)
+}
+
+// Remember where the keyfile lives (e.g. a USB stick mount) and unlock with it
+#[tauri::command]
+async fn unlock_with_keyfile(master_password: String, keyfile_path: String) -> Result<(), String> {
+    // Check the keyfile against the vault before remembering it, so a wrong
+    // file never replaces a working path
+    let expected_check = load_encrypted_store()?
+        .keyfile_check
+        .ok_or_else(|| "This vault does not use a keyfile".to_string())?;
+    if keyfile_check(&keyfile_digest(&keyfile_path)?) != expected_check {
+        return Err(KEYFILE_MISMATCH_ERROR.to_string());
+    }
+
+    let mut settings = load_settings()?;
+    settings.keyfile_path = Some(keyfile_path);
+    save_settings(&settings)?;
+
+    unlock_vault(master_password).await
+}
+
+// Checks the password and returns the vault key. Never touches the session:
+// only the unlock commands below start one.
+fn derive_master_key(password: &str) -> Result<Vec<u8>, String> {
+    if let Some(error) = SELF_TEST_FAILURE.lock().unwrap().clone() {
+        return Err(error);
+    }
+
+    let hash_path = get_master_hash_path()?;
+    if !hash_path.exists() {
+        return Err("Master password not set".to_string());
+    }
+
+    finish_interrupted_rekey(password)?;
+
+    let stored_hash = fs::read_to_string(&hash_path)
+        .map_err(|e| format!("Failed to read master password hash: {}", e))?;
+
+    let parsed_hash = PasswordHash::new(&stored_hash)
+        .map_err(|e| format!("Failed to parse password hash: {}", e))?;
+
+    let argon2 = Argon2::default();
+    if argon2
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .is_err()
+    {
+        let failures = UNLOCK_FAILURES.fetch_add(1, Ordering::SeqCst) + 1;
+        tracing::warn!(failures, "Master password verification failed");
+        return Err(WRONG_KEY_ERROR.to_string());
+    }
+
+    let successes = UNLOCK_SUCCESSES.fetch_add(1, Ordering::SeqCst) + 1;
+    tracing::debug!(successes, "Master password verified");
+
+    // Generate and return the key
+    let salt = parsed_hash.salt.unwrap().as_str().as_bytes();
+    let params = argon2::Params::try_from(&parsed_hash)
+        .map_err(|e| format!("Failed to read KDF parameters: {}", e))?;
+    let upgrade = upgraded_params(&params);
+    let key = apply_keyfile(generate_key_with_params(password, salt, params)?)?;
+
+    // A failed upgrade leaves the old hash and vault intact, so unlock anyway
+    if let Some(upgrade) = upgrade {
+        if kdf_upgrade_due() && get_data_file_path()?.exists() {
+            match upgrade_kdf(password, &key, upgrade) {
+                Ok(new_key) => {
+                    *KDF_UPGRADE_FAILURES.lock().unwrap() = None;
+                    return Ok(new_key);
+                }
+                Err(e) => {
+                    record_kdf_upgrade_failure();
+                    tracing::warn!("Failed to upgrade KDF parameters: {}", e);
+                }
+            }
+        }
+    }
+
+    Ok(key)
+}
+
+// Re-authentication check (e.g. before showing a password); doesn't unlock
+#[tauri::command]
+async fn verify_master_password(password: String) -> Result<(), String> {
+    derive_master_key(&password).map(|_| ())
+}
+
+// Only actual unlock attempts go in the audit log, not re-authentication
+// prompts, so failures and successes are counted the same way
+fn unlock_with_password(master_password: &str) -> Result<zeroize::Zeroizing<Vec<u8>>, String> {
+    let key = match derive_master_key(master_password) {
+        Ok(key) => zeroize::Zeroizing::new(key),
+        Err(e) => {
+            if e == WRONG_KEY_ERROR {
+                record_unlock_attempt(false, "password");
+            }
+            return Err(e);
+        }
+    };
+    start_session(&key, "password");
+    Ok(key)
+}
+
+// Password unlock. Afterwards commands can pass no master password and run
+// against the session.
+#[tauri::command]
+async fn unlock_vault(master_password: String) -> Result<(), String> {
+    unlock_with_password(&master_password).map(|_| ())
+}
+
+#[tauri::command]
+async fn has_master_password() -> Result<bool, String> {
+    let hash_path = get_master_hash_path()?;
+    Ok(hash_path.exists())
+}
+
+// Vault key cached after a successful unlock (password, Windows Hello, ...)
+lazy_static::lazy_static! {
+    static ref SESSION_KEY: Mutex<Option<zeroize::Zeroizing<Vec<u8>>>> = Mutex::new(None);
+}
+
+fn set_session_key(key: &[u8]) {
+    *SESSION_KEY.lock().unwrap() = Some(zeroize::Zeroizing::new(key.to_vec()));
+}
+
+// Only the unlock commands call this; re-unlocking an open session isn't
+// logged as another unlock
+fn start_session(key: &[u8], method: &str) {
+    if SESSION_KEY.lock().unwrap().is_none() {
+        record_unlock_attempt(true, method);
+    }
+    set_session_key(key);
+}
+
+// A rekey changes the vault key; an open session must follow it, but a rekey
+// never opens one
+fn refresh_session_key(key: &[u8]) {
+    let mut session = SESSION_KEY.lock().unwrap();
+    if session.is_some() {
+        *session = Some(zeroize::Zeroizing::new(key.to_vec()));
+    }
+}
+
+// No master password means "use the unlocked session"; a password given
+// explicitly is always checked, never swapped for the session
+fn vault_key(master_password: Option<&str>) -> Result<Vec<u8>, String> {
+    record_activity();
+    match master_password {
+        Some(password) => derive_master_key(password),
+        None => SESSION_KEY
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|key| key.to_vec())
+            .ok_or_else(|| "Vault is locked".to_string()),
+    }
+}
+
+// Decrypted store kept for the unlocked session, keyed by id for O(1) lookups.
+// `order` preserves the on-disk ordering for listing.
+struct UnlockedStore {
+    entries: HashMap<u32, PasswordEntry>,
+    order: Vec<u32>,
+    next_id: u32,
+    created_at: String,
+    last_backup: Option<String>,
+}
+
+impl UnlockedStore {
+    fn from_store(store: &PasswordStore) -> Self {
+        Self {
+            entries: store.entries.iter().map(|e| (e.id, e.clone())).collect(),
+            order: store.entries.iter().map(|e| e.id).collect(),
+            next_id: store.next_id,
+            created_at: store.created_at.clone(),
+            last_backup: store.last_backup.clone(),
+        }
+    }
+
+    fn to_store(&self) -> PasswordStore {
+        PasswordStore {
+            entries: self
+                .order
+                .iter()
+                .filter_map(|id| self.entries.get(id).cloned())
+                .collect(),
+            next_id: self.next_id,
+            created_at: self.created_at.clone(),
+            last_backup: self.last_backup.clone(),
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref UNLOCKED_STORE: Mutex<Option<UnlockedStore>> = Mutex::new(None);
+}
+
+// Only session callers (no master password) are served from the cache; an
+// explicit master password is always checked against the vault
+fn cache_unlocked_store(store: &PasswordStore) {
+    if SESSION_KEY.lock().unwrap().is_some() {
+        *UNLOCKED_STORE.lock().unwrap() = Some(UnlockedStore::from_store(store));
+    }
+}
+
+fn find_entry(id: u32, master_password: Option<&str>) -> Result<Option<PasswordEntry>, String> {
+    if master_password.is_none() {
+        if let Some(cache) = UNLOCKED_STORE.lock().unwrap().as_ref() {
+            record_activity();
+            return Ok(cache.entries.get(&id).cloned());
+        }
+    }
+
+    let store = load_password_store(master_password)?;
+    Ok(store.entries.into_iter().find(|e| e.id == id))
+}
+
+// Read-only pass over the vault in on-disk order. With a warm session cache
+// the entries are borrowed from it, so nothing is copied or decrypted.
+fn with_entries<R>(
+    master_password: Option<&str>,
+    f: impl FnOnce(&[&PasswordEntry]) -> R,
+) -> Result<R, String> {
+    if master_password.is_none() {
+        if let Some(cache) = UNLOCKED_STORE.lock().unwrap().as_ref() {
+            record_activity();
+            let entries: Vec<&PasswordEntry> = cache
+                .order
+                .iter()
+                .filter_map(|id| cache.entries.get(id))
+                .collect();
+            return Ok(f(&entries));
+        }
+    }
+
+    let store = load_password_store(master_password)?;
+    let entries: Vec<&PasswordEntry> = store.entries.iter().collect();
+    Ok(f(&entries))
+}
+
+// Encrypted store functions (keeping existing functions)
+fn save_encrypted_store(store: &EncryptedPasswordStore) -> Result<(), String> {
+    let file_path = get_data_file_path()?;
+    let content = serde_json::to_string_pretty(store)
+        .map_err(|e| format!("Failed to serialize encrypted store: {}", e))?;
+
+    write_atomically(&file_path, content.as_bytes())?;
+    remember_vault_fingerprint(&content);
+    Ok(())
+}
+
+// Hash of the vault file as this process last read or wrote it. A save that
+// finds something else on disk means another process (or a sync client)
+// changed the vault underneath us.
+lazy_static::lazy_static! {
+    static ref VAULT_FINGERPRINT: Mutex<Option<String>> = Mutex::new(None);
+}
+
+const CONFLICT_ERROR_PREFIX: &str = "ConflictDetected";
+
+fn vault_fingerprint(content: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    general_purpose::STANDARD.encode(Sha256::digest(content.as_bytes()))
+}
+
+fn remember_vault_fingerprint(content: &str) {
+    *VAULT_FINGERPRINT.lock().unwrap() = Some(vault_fingerprint(content));
+}
+
+// Write to a sibling temp file and rename over the target, so readers never see a half-written file
+fn write_atomically(path: &std::path::Path, content: &[u8]) -> Result<(), String> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, content)
+        .map_err(|e| format!("Failed to write {}: {}", tmp_path.display(), e))?;
+    fs::rename(&tmp_path, path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
+
+// write_atomically for secrets: the temp file is created owner-only, so the
+// content is never readable by other users, not even briefly
+fn write_private_atomically(path: &std::path::Path, content: &[u8]) -> Result<(), String> {
+    use std::io::Write;
+
+    let tmp_path = path.with_extension("tmp");
+    let _ = fs::remove_file(&tmp_path);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&tmp_path)
+        .and_then(|mut file| file.write_all(content))
+        .map_err(|e| format!("Failed to write {}: {}", tmp_path.display(), e))?;
+    fs::rename(&tmp_path, path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
+
+fn load_encrypted_store() -> Result<EncryptedPasswordStore, String> {
+    load_encrypted_store_with_content().map(|(store, _)| store)
+}
+
+// The raw file content comes back too, for fingerprinting
+fn load_encrypted_store_with_content() -> Result<(EncryptedPasswordStore, String), String> {
+    let file_path = get_data_file_path()?;
+
+    if !file_path.exists() {
+        return Err("Encrypted store not found".to_string());
+    }
+
+    let content = fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read encrypted store: {}", e))?;
+
+    match serde_json::from_str(&content) {
+        Ok(store) => Ok((store, content)),
+        // Zero bytes or cut off mid-write (crash, sync conflict): try a backup
+        Err(e) if content.trim().is_empty() || e.is_eof() || e.is_syntax() => {
+            tracing::error!("Vault file is empty or truncated: {}", e);
+            recover_vault_from_backup()
+        }
+        Err(e) => Err(format!("Failed to parse encrypted store: {}", e)),
+    }
+}
+
+#[derive(serde::Serialize, Clone)]
+#[serde(tag = "status")]
+enum VaultHealth {
+    Healthy,
+    Missing,
+    RecoveredFromBackup {
+        source: String,
+        // The damaged file is kept next to the vault rather than deleted
+        damaged_copy: String,
+    },
+}
+
+lazy_static::lazy_static! {
+    // Set once a damaged vault has been replaced, reported by check_vault_health
+    static ref VAULT_RECOVERY: Mutex<Option<VaultHealth>> = Mutex::new(None);
+}
+
+// vault.cocoon.bak first, then backups/ newest first. Ones sealed under the
+// current master.hash salt are preferred, since older ones won't open.
+fn recovery_candidates() -> Result<Vec<PathBuf>, String> {
+    let mut candidates = vec![get_data_file_path()?.with_extension("cocoon.bak")];
+
+    if let Ok(read_dir) = fs::read_dir(get_backup_dir()?) {
+        let mut backups: Vec<PathBuf> = read_dir
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "cocoon"))
+            .collect();
+        // Backup names embed a sortable timestamp
+        backups.sort();
+        candidates.extend(backups.into_iter().rev());
+    }
+
+    let live_salt = master_hash_salt().ok().map(|(salt, _)| salt);
+    let mut parsed: Vec<(bool, PathBuf)> = candidates
+        .into_iter()
+        .filter_map(|path| {
+            let content = fs::read_to_string(&path).ok()?;
+            let store: EncryptedPasswordStore = serde_json::from_str(&content).ok()?;
+            Some((live_salt.as_deref() == Some(store.salt.as_str()), path))
+        })
+        .collect();
+    // Stable, so newest-first order is kept within each group
+    parsed.sort_by_key(|(salt_matches, _)| !salt_matches);
+
+    Ok(parsed.into_iter().map(|(_, path)| path).collect())
+}
+
+fn recover_vault_from_backup() -> Result<(EncryptedPasswordStore, String), String> {
+    let file_path = get_data_file_path()?;
+    let source = recovery_candidates()?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "Vault file is damaged and no usable backup was found".to_string())?;
+
+    let damaged_copy = file_path.with_extension(format!(
+        "cocoon.damaged-{}",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+    fs::rename(&file_path, &damaged_copy)
+        .map_err(|e| format!("Failed to move damaged vault aside: {}", e))?;
+
+    let content = fs::read_to_string(&source)
+        .map_err(|e| format!("Failed to read backup {}: {}", source.display(), e))?;
+    write_atomically(&file_path, content.as_bytes())?;
+    let store = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse encrypted store: {}", e))?;
+
+    tracing::warn!(source = %source.display(), "Recovered damaged vault from backup");
+    *VAULT_RECOVERY.lock().unwrap() = Some(VaultHealth::RecoveredFromBackup {
+        source: source.to_string_lossy().to_string(),
+        damaged_copy: damaged_copy.to_string_lossy().to_string(),
+    });
+    Ok((store, content))
+}
+
+// Run at startup: triggers recovery if needed and tells the UI it happened
+#[tauri::command]
+async fn check_vault_health() -> Result<VaultHealth, String> {
+    if !get_data_file_path()?.exists() {
+        return Ok(VaultHealth::Missing);
+    }
+
+    load_encrypted_store()?;
+    Ok(VAULT_RECOVERY
+        .lock()
+        .unwrap()
+        .take()
+        .unwrap_or(VaultHealth::Healthy))
+}
+
+const VAULT_FORMAT_VERSION: u8 = 1;
+
+// Distinct messages so the UI can tell a wrong key from a vault it can't read
+const WRONG_KEY_ERROR: &str = "Invalid master password";
+const NEWER_VAULT_ERROR: &str = "Vault was created by a newer version of Cocoon";
+
+fn load_password_store(master_password: Option<&str>) -> Result<PasswordStore, String> {
+    if master_password.is_none() {
+        if let Some(cache) = UNLOCKED_STORE.lock().unwrap().as_ref() {
+            record_activity();
+            return Ok(cache.to_store());
+        }
+    }
+
+    let key = zeroize::Zeroizing::new(vault_key(master_password)?);
+    load_password_store_with_key(&key)
+}
+
+// Decrypts the vault with an already-derived key, for callers that need the
+// key for something else too (e.g. the search index)
+fn load_password_store_with_key(key: &[u8]) -> Result<PasswordStore, String> {
+    let (encrypted_store, content) = load_encrypted_store_with_content()?;
+
+    if encrypted_store.version > VAULT_FORMAT_VERSION {
+        return Err(NEWER_VAULT_ERROR.to_string());
+    }
+
+    // A GCM tag mismatch only ever means the key is wrong (or the file was tampered with)
+    let plaintext = decrypt_bytes(
+        &encrypted_store.encrypted_data,
+        &encrypted_store.nonce,
+        key,
+    )
+    .map_err(|e| {
+        tracing::warn!("Vault decryption failed: {}", e);
+        WRONG_KEY_ERROR.to_string()
+    })?;
+
+    // The version check above already caught newer formats, so anything
+    // failing here is damage, not a version mismatch
+    let decrypted_data = unseal_store_json(plaintext, encrypted_store.compressed).map_err(|e| {
+        tracing::error!("Decrypted vault could not be read: {}", e);
+        format!("Vault is corrupt: {}", e)
+    })?;
+    let store: PasswordStore = serde_json::from_str(&decrypted_data).map_err(|e| {
+        tracing::error!("Decrypted vault could not be parsed: {}", e);
+        format!("Vault is corrupt: {}", e)
+    })?;
+
+    remember_vault_fingerprint(&content);
+    cache_unlocked_store(&store);
+    Ok(store)
+}
+
+// Refuses to overwrite a vault that changed on disk since this process last
+// read or wrote it
+fn check_vault_unchanged(existing: &EncryptedPasswordStore, content: &str) -> Result<(), String> {
+    let expected = VAULT_FINGERPRINT.lock().unwrap().clone();
+    if expected.is_some_and(|expected| expected != vault_fingerprint(content)) {
+        // Force the next load to read what's on disk now
+        UNLOCKED_STORE.lock().unwrap().take();
+        return Err(format!(
+            "{}: the vault was changed by another process (revision {} on disk); reload before saving",
+            CONFLICT_ERROR_PREFIX, existing.revision
+        ));
+    }
+    Ok(())
+}
+
+fn save_password_store(store: &PasswordStore, master_password: Option<&str>) -> Result<(), String> {
+    let key = vault_key(master_password)?;
+    let store_json =
+        serde_json::to_string(store).map_err(|e| format!("Failed to serialize store: {}", e))?;
+
+    let (encrypted_data, nonce, compressed) = seal_store_json(&store_json, &key)?;
+
+    // Load existing encrypted store to preserve salt and other metadata
+    let mut encrypted_store = match load_encrypted_store_with_content() {
+        Ok((existing, content)) => {
+            check_vault_unchanged(&existing, &content)?;
+            existing
+        }
+        // Create new encrypted store if none exists
+        Err(_) => EncryptedPasswordStore {
+            encrypted_data: String::new(),
+            nonce: String::new(),
+            salt: String::new(),
+            iterations: 100_000,
+            version: VAULT_FORMAT_VERSION,
+            keyfile_check: None,
+            revision: 0,
+            compressed: false,
+            kdf_params: None,
+        },
+    };
+
+    encrypted_store.encrypted_data = encrypted_data;
+    encrypted_store.nonce = nonce;
+    encrypted_store.compressed = compressed;
+    encrypted_store.revision += 1;
+    // The live key always follows master.hash; record it for future backups
+    if encrypted_store.kdf_params.is_none() {
+        encrypted_store.kdf_params = current_kdf_params().ok().map(|params| kdf_params_header(&params));
+    }
+
+    save_encrypted_store(&encrypted_store)?;
+    cache_unlocked_store(store);
+
+    // A stale index is only slower, never wrong: search checks the revision
+    if let Err(e) = write_search_index(store, &key, encrypted_store.revision) {
+        tracing::warn!("Failed to update search index: {}", e);
+    }
+
+    if load_settings().map(|s| s.locked_preview).unwrap_or(false) {
+        if let Err(e) = write_title_preview(store) {
+            tracing::warn!("Failed to refresh locked preview: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+// Search index: the fields search ranks on and result rows show, encrypted
+// under the vault key in index.cocoon, so a cold search never decrypts the vault
+#[derive(serde::Serialize, serde::Deserialize)]
+struct IndexRecord {
+    id: u32,
+    title: String,
+    username: String,
+    url: Option<String>,
+    modified_at: String,
+    // Searchable custom fields only
+    #[serde(default)]
+    custom_fields: Vec<CustomField>,
+    password_strength: u8,
+    color: Option<String>,
+    glyph: Option<String>,
+    sort_index: Option<i32>,
+    archived: bool,
+}
+
+// Bumped when IndexRecord changes; older indexes are ignored until the next save
+const SEARCH_INDEX_FORMAT: u8 = 2;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct EncryptedSearchIndex {
+    encrypted_data: String,
+    nonce: String,
+    revision: u64,
+    #[serde(default)]
+    format: u8,
+}
+
+fn get_search_index_path() -> Result<PathBuf, String> {
+    Ok(get_vault_dir()?.join("index.cocoon"))
+}
+
+fn write_search_index(store: &PasswordStore, key: &[u8], revision: u64) -> Result<(), String> {
+    let records: Vec<IndexRecord> = store
+        .entries
+        .iter()
+        .map(|entry| IndexRecord {
+            id: entry.id,
+            title: entry.title.clone(),
+            username: entry.username.clone(),
+            url: entry.url.clone(),
+            modified_at: entry.modified_at.clone(),
+            custom_fields: entry
+                .custom_fields
+                .iter()
+                .filter(|field| field.searchable)
+                .cloned()
+                .collect(),
+            password_strength: entry.password_strength,
+            color: entry.color.clone(),
+            glyph: entry.glyph.clone(),
+            sort_index: entry.sort_index,
+            archived: entry.archived,
+        })
+        .collect();
+    let records_json =
+        serde_json::to_string(&records).map_err(|e| format!("Failed to serialize index: {}", e))?;
+    let (encrypted_data, nonce) = encrypt_data(&records_json, key)?;
+
+    let content = serde_json::to_string(&EncryptedSearchIndex {
+        encrypted_data,
+        nonce,
+        revision,
+        format: SEARCH_INDEX_FORMAT,
+    })
+    .map_err(|e| format!("Failed to serialize index: {}", e))?;
+    write_atomically(&get_search_index_path()?, content.as_bytes())
+}
+
+// None when the index is missing, behind the vault, from an older build, or
+// unreadable with this key
+fn load_search_index(key: &[u8]) -> Result<Option<Vec<IndexRecord>>, String> {
+    let index_path = get_search_index_path()?;
+    if !index_path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(&index_path)
+        .map_err(|e| format!("Failed to read search index: {}", e))?;
+    let index: EncryptedSearchIndex = match serde_json::from_str(&content) {
+        Ok(index) => index,
+        Err(_) => return Ok(None),
+    };
+    if index.format != SEARCH_INDEX_FORMAT || index.revision != load_encrypted_store()?.revision {
+        return Ok(None);
+    }
+
+    let records = decrypt_data(&index.encrypted_data, &index.nonce, key)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok());
+    Ok(records)
+}
+
+// Entries rebuilt from the index with just the indexed fields, enough to rank
+// and show result rows. None to fall back to the vault. Notes aren't indexed.
+fn indexed_entries(
+    searchable: &std::collections::HashSet<FieldKind>,
+    key: &[u8],
+) -> Result<Option<Vec<PasswordEntry>>, String> {
+    if searchable.contains(&FieldKind::Notes) {
+        return Ok(None);
+    }
+
+    let records = match load_search_index(key)? {
+        Some(records) => records,
+        None => return Ok(None),
+    };
+    Ok(Some(
+        records
+            .into_iter()
+            .map(|record| PasswordEntry {
+                id: record.id,
+                title: record.title,
+                username: record.username,
+                url: record.url,
+                modified_at: record.modified_at,
+                custom_fields: record.custom_fields,
+                password_strength: record.password_strength,
+                color: record.color,
+                glyph: record.glyph,
+                sort_index: record.sort_index,
+                archived: record.archived,
+                ..Default::default()
+            })
+            .collect(),
+    ))
+}
+
+// Locked preview: entry titles only, readable before unlock. They are stored
+// unencrypted: any key kept next to them would protect nothing, so the setting
+// is an explicit trade of title privacy for searching while locked.
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+struct TitlePreview {
+    id: u32,
+    title: String,
+}
+
+fn get_title_preview_path() -> Result<PathBuf, String> {
+    Ok(get_app_data_dir()?.join("titles.json"))
+}
+
+// Older builds kept the titles under a key stored right beside them
+fn remove_legacy_title_preview() -> Result<(), String> {
+    let app_data_dir = get_app_data_dir()?;
+    let _ = fs::remove_file(app_data_dir.join("preview.key"));
+    let _ = fs::remove_file(app_data_dir.join("titles.cocoon"));
+    Ok(())
+}
+
+fn write_title_preview(store: &PasswordStore) -> Result<(), String> {
+    let titles: Vec<TitlePreview> = store
+        .entries
+        .iter()
+        .map(|e| TitlePreview {
+            id: e.id,
+            title: e.title.clone(),
+        })
+        .collect();
+    let titles_json = serde_json::to_string(&titles)
+        .map_err(|e| format!("Failed to serialize preview: {}", e))?;
+
+    write_atomically(&get_title_preview_path()?, titles_json.as_bytes())?;
+    remove_legacy_title_preview()
+}
+
+fn read_title_preview() -> Result<Vec<TitlePreview>, String> {
+    let content = fs::read_to_string(get_title_preview_path()?)
+        .map_err(|e| format!("Failed to read preview: {}", e))?;
+
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse preview: {}", e))
+}
+
+#[tauri::command]
+async fn set_locked_preview(enabled: bool, master_password: Option<String>) -> Result<(), String> {
+    if enabled {
+        let store = load_password_store(master_password.as_deref())?;
+        write_title_preview(&store)?;
+    } else {
+        let _ = fs::remove_file(get_title_preview_path()?);
+        remove_legacy_title_preview()?;
+    }
+
+    let mut settings = load_settings()?;
+    settings.locked_preview = enabled;
+    save_settings(&settings)
+}
+
+// Title matches without the master password; autofill still requires unlocking
+#[tauri::command]
+async fn locked_preview_search(query: String) -> Result<Vec<TitlePreview>, String> {
+    if !load_settings()?.locked_preview {
+        return Err("Locked preview is disabled".to_string());
+    }
+
+    let query = query.to_lowercase();
+    Ok(read_title_preview()?
+        .into_iter()
+        .filter(|t| t.title.to_lowercase().contains(&query))
+        .take(DEFAULT_SEARCH_LIMIT)
+        .collect())
+}
+
+fn calculate_password_strength(password: &str) -> u8 {
+    let mut score = 0u8;
+
+    // Length scoring
+    if password.len() >= 8 {
+        score += 20;
+    }
+    if password.len() >= 12 {
+        score += 15;
+    }
+    if password.len() >= 16 {
+        score += 10;
+    }
+
+    // Character variety
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        score += 5;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        score += 5;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        score += 5;
+    }
+    if password
+        .chars()
+        .any(|c| "!@#$%^&*()_+-=[]{}|;:,.<>?".contains(c))
+    {
+        score += 10;
+    }
+
+    // Complexity bonus
+    let unique_chars = password
+        .chars()
+        .collect::<std::collections::HashSet<_>>()
+        .len();
+    if unique_chars > password.len() / 2 {
+        score += 10;
+    }
+
+    // Penalty for common patterns
+    if password.to_lowercase().contains("password")
+        || password.to_lowercase().contains("123456")
+        || password
+            .chars()
+            .collect::<Vec<_>>()
+            .windows(3)
+            .any(|w| w[0] == w[1] && w[1] == w[2])
+    {
+        score = score.saturating_sub(20);
+    }
+
+    score.min(100)
+}
+
+#[tauri::command]
+async fn auto_fill_credentials_spotlight_with_login(
+    entry_id: u32,
+    master_password: Option<String>,
+    press_enter: bool,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let entry = find_entry(entry_id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+    begin_autofill("auto_fill_credentials_spotlight_with_login");
+
+    finish_autofill(&app_handle, fill_credentials(&app_handle, &entry, press_enter))
+}
+
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+
+// Rank a match by the most important field it hits: title > username > url/notes
+// Fields outside `searchable` never match, so they can't surface an entry
+fn search_match_weight(
+    entry: &PasswordEntry,
+    query: &str,
+    searchable: &std::collections::HashSet<FieldKind>,
+) -> u8 {
+    let contains = |kind: FieldKind, field: Option<&str>| {
+        searchable.contains(&kind) && field.is_some_and(|f| f.to_lowercase().contains(query))
+    };
+
+    if contains(FieldKind::Title, Some(&entry.title)) {
+        3
+    } else if contains(FieldKind::Username, Some(&entry.username)) {
+        2
+    } else if contains(FieldKind::Url, entry.url.as_deref())
+        || contains(FieldKind::Notes, entry.notes.as_deref())
+        || entry
+            .custom_fields
+            .iter()
+            .any(|field| field.searchable && field.value.to_lowercase().contains(query))
+    {
+        1
+    } else {
+        0
+    }
+}
+
+// Matching entries, best match first (recency breaks ties); empty query = recent
+fn rank_entries<'a>(
+    entries: impl IntoIterator<Item = &'a PasswordEntry>,
+    query: &str,
+    searchable: &std::collections::HashSet<FieldKind>,
+) -> Vec<&'a PasswordEntry> {
+    if query.is_empty() {
+        // Most recently touched entries first
+        let mut entries: Vec<&PasswordEntry> = entries.into_iter().collect();
+        entries.sort_by(|a, b| b.modified_at.cmp(&a.modified_at));
+        return entries;
+    }
+
+    let query = query.to_lowercase();
+    let mut ranked_entries: Vec<(u8, &PasswordEntry)> = entries
+        .into_iter()
+        .map(|entry| (search_match_weight(entry, &query, searchable), entry))
+        .filter(|(weight, _)| *weight > 0)
+        .collect();
+
+    ranked_entries.sort_by(|(weight_a, a), (weight_b, b)| {
+        weight_b
+            .cmp(weight_a)
+            .then_with(|| b.modified_at.cmp(&a.modified_at))
+    });
+
+    ranked_entries.into_iter().map(|(_, entry)| entry).collect()
+}
+
+// eTLD+1 of a URL-ish string per the Public Suffix List ("mail.google.com" ->
+// "google.com", "a.example.co.uk" -> "example.co.uk"). IPs and hosts that are
+// themselves a public suffix (e.g. "github.io", "localhost") come back whole.
+fn registrable_domain(url: &str) -> Option<String> {
+    let normalized = normalize_url(url);
+    let parsed = url::Url::parse(&normalized).ok()?;
+    let host = match parsed.host()? {
+        url::Host::Domain(domain) => domain.trim_end_matches('.').to_lowercase(),
+        ip => return Some(ip.to_string()),
+    };
+    if host.is_empty() {
+        return None;
+    }
+
+    Some(psl::domain_str(&host).map(str::to_string).unwrap_or(host))
+}
+
+// Exposed so the UI groups by the same notion of "site" as search and import
+#[tauri::command]
+async fn normalize_domain(url: String) -> Result<Option<String>, String> {
+    Ok(registrable_domain(&url))
+}
+
+#[derive(serde::Serialize)]
+struct ResultGroup {
+    domain: String,
+    count: usize,
+    entry_ids: Vec<u32>,
+}
+
+// Row fields are flattened so callers can treat results like list rows; the
+// full entry (password, TOTP, history) comes from get_entry_by_id
+#[derive(serde::Serialize)]
+struct SearchResult {
+    #[serde(flatten)]
+    entry: EntrySummary,
+    display_title: String,
+    group: Option<ResultGroup>,
+}
+
+#[tauri::command(async)]
+async fn search_entries(
+    query: String,
+    limit: Option<usize>,
+    disambiguate_titles: Option<bool>,
+    group_by_domain: Option<bool>,
+    sort: Option<SortOrder>,
+    include_archived: Option<bool>,
+    master_password: Option<String>,
+) -> Result<Vec<SearchResult>, String> {
+    let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+    let searchable = load_settings()?.searchable_fields;
+    let include_archived = include_archived.unwrap_or(false);
+    let group_by_domain = group_by_domain.unwrap_or(false);
+
+    // A warm session cache beats the index. Otherwise the rows come from the
+    // index, and the vault is only decrypted when the index can't be used.
+    let cache_warm = master_password.is_none() && UNLOCKED_STORE.lock().unwrap().is_some();
+    let key = if cache_warm {
+        None
+    } else {
+        Some(zeroize::Zeroizing::new(vault_key(master_password.as_deref())?))
+    };
+
+    // Ranking runs over borrowed entries; only rows that can end up in the
+    // results are copied out of the vault, and never their secrets
+    let select = |entries: &[&PasswordEntry]| -> Vec<EntrySummary> {
+        let mut ranked = rank_entries(entries.iter().copied(), &query, &searchable);
+        if !include_archived {
+            ranked.retain(|entry| !entry.archived);
+        }
+        // Without an explicit order, results stay in relevance order
+        if let Some(sort) = sort {
+            sort_entries(&mut ranked, sort);
+        }
+        // Grouped results are limited after grouping, below
+        if !group_by_domain {
+            ranked.truncate(limit);
+        }
+        ranked.into_iter().map(entry_summary).collect()
+    };
+    let ranked = match &key {
+        Some(key) => match indexed_entries(&searchable, key)? {
+            Some(entries) => select(&entries.iter().collect::<Vec<_>>()),
+            None => {
+                let store = load_password_store_with_key(key)?;
+                select(&store.entries.iter().collect::<Vec<_>>())
+            }
+        },
+        None => with_entries(None, select)?,
+    };
+
+    let mut results: Vec<SearchResult> = Vec::new();
+    if group_by_domain {
+        // One row per domain, led by its best-ranked entry
+        let mut group_index: HashMap<String, usize> = HashMap::new();
+        for entry in ranked {
+            let domain = entry.url.as_deref().and_then(registrable_domain);
+
+            if let Some(&index) = domain.as_ref().and_then(|d| group_index.get(d)) {
+                let lead_id = results[index].entry.id;
+                let group = results[index].group.get_or_insert_with(|| ResultGroup {
+                    domain: domain.clone().unwrap_or_default(),
+                    count: 1,
+                    entry_ids: vec![lead_id],
+                });
+                group.count += 1;
+                group.entry_ids.push(entry.id);
+                continue;
+            }
+
+            if let Some(domain) = domain {
+                group_index.insert(domain, results.len());
+            }
+            results.push(SearchResult {
+                display_title: entry.title.clone(),
+                entry,
+                group: None,
+            });
+        }
+    } else {
+        results = ranked
+            .into_iter()
+            .map(|entry| SearchResult {
+                display_title: entry.title.clone(),
+                entry,
+                group: None,
+            })
+            .collect();
+    }
+
+    results.truncate(limit);
+
+    if disambiguate_titles.unwrap_or(false) {
+        let mut title_counts: HashMap<String, usize> = HashMap::new();
+        for result in &results {
+            *title_counts.entry(result.entry.title.to_lowercase()).or_default() += 1;
+        }
+
+        for result in results.iter_mut() {
+            let is_duplicate = title_counts[&result.entry.title.to_lowercase()] > 1;
+            if is_duplicate && !result.entry.username.is_empty() {
+                result.display_title = format!("{} ({})", result.entry.title, result.entry.username);
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+// Canonicalize URL-like input (scheme, lowercase host, no trailing slash);
+// free text such as "Gmail" or "work VPN" is kept as typed
+fn normalize_url(raw: &str) -> String {
+    let trimmed = raw.trim();
+    let has_scheme = trimmed.contains("://");
+    let looks_like_host = !trimmed.contains(char::is_whitespace)
+        && (trimmed.contains('.') || trimmed.to_lowercase().starts_with("localhost"));
+
+    if !has_scheme && !looks_like_host {
+        return trimmed.to_string();
+    }
+
+    let candidate = if has_scheme {
+        trimmed.to_string()
+    } else {
+        format!("https://{}", trimmed)
+    };
+
+    match url::Url::parse(&candidate) {
+        Ok(parsed) if parsed.host_str().is_some() => {
+            let normalized = parsed.to_string();
+            if parsed.query().is_none() && parsed.fragment().is_none() {
+                normalized.trim_end_matches('/').to_string()
+            } else {
+                normalized
+            }
+        }
+        _ => trimmed.to_string(),
+    }
+}
+
+fn normalize_optional_url(url: Option<String>) -> Option<String> {
+    url.map(|u| normalize_url(&u)).filter(|u| !u.is_empty())
+}
+
+const MAX_GLYPH_CHARS: usize = 8;
+
+// Accepts #rgb or #rrggbb and stores the lowercase long form
+fn validate_color(color: Option<String>) -> Result<Option<String>, String> {
+    let color = match color.map(|c| c.trim().to_string()).filter(|c| !c.is_empty()) {
+        Some(color) => color,
+        None => return Ok(None),
+    };
+
+    let hex = color
+        .strip_prefix('#')
+        .filter(|hex| (hex.len() == 3 || hex.len() == 6) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(|| format!("Invalid color '{}': expected a hex value like #3a7bd5", color))?;
+
+    let hex = if hex.len() == 3 {
+        hex.chars().flat_map(|c| [c, c]).collect()
+    } else {
+        hex.to_string()
+    };
+    Ok(Some(format!("#{}", hex.to_lowercase())))
+}
+
+// Grapheme clusters can span several chars (flags, skin tones), so only the
+// overall length is capped
+fn validate_glyph(glyph: Option<String>) -> Result<Option<String>, String> {
+    match glyph.map(|g| g.trim().to_string()).filter(|g| !g.is_empty()) {
+        Some(glyph) if glyph.chars().count() > MAX_GLYPH_CHARS => {
+            Err("Glyph must be a single emoji or a few characters".to_string())
+        }
+        glyph => Ok(glyph),
+    }
+}
+
+#[tauri::command]
+async fn add_entry(
+    title: String,
+    username: String,
+    password: String,
+    url: Option<String>,
+    notes: Option<String>,
+    color: Option<String>,
+    glyph: Option<String>,
+    totp: Option<TotpSettings>,
+    master_password: Option<String>,
+) -> Result<u32, String> {
+    let color = validate_color(color)?;
+    let glyph = validate_glyph(glyph)?;
+    let totp = totp.map(validate_totp_settings).transpose()?;
+    let mut store = load_password_store(master_password.as_deref())?;
+    let password_strength = calculate_password_strength(&password);
+    let url = normalize_optional_url(url);
+
+    let mut entry = PasswordEntry {
+        id: store.next_id,
+        title,
+        username,
+        password,
+        url,
+        notes,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        modified_at: chrono::Utc::now().to_rfc3339(),
+        password_strength,
+        color,
+        glyph,
+        password_changed_at: Some(chrono::Utc::now().to_rfc3339()),
+        ..Default::default()
+    };
+    if let Some(totp) = totp {
+        totp.apply_to(&mut entry);
+    }
+
+    let entry_id = entry.id;
+    store.entries.push(entry);
+    store.next_id += 1;
+
+    save_password_store(&store, master_password.as_deref())?;
+
+    Ok(entry_id)
+}
+
+#[derive(serde::Serialize)]
+struct CreatedEntry {
+    id: u32,
+    password: String,
+}
+
+// Quick capture for sign-up forms: generate, create and save in one round trip
+#[tauri::command]
+async fn create_entry_with_generated_password(
+    title: String,
+    username: String,
+    url: Option<String>,
+    gen_config: PasswordGenConfig,
+    master_password: Option<String>,
+) -> Result<CreatedEntry, String> {
+    let password = generate_from_config(&gen_config)?;
+    let mut store = load_password_store(master_password.as_deref())?;
+
+    let mut entry = new_login_entry(title, username, password.clone(), url, None);
+    entry.id = store.next_id;
+    let id = entry.id;
+    store.entries.push(entry);
+    store.next_id += 1;
+
+    save_password_store(&store, master_password.as_deref())?;
+
+    Ok(CreatedEntry { id, password })
+}
+
+// Sets a new password, keeping the old one in history. Saving the same
+// password again leaves password_changed_at (and so its age) alone.
+fn replace_password(entry: &mut PasswordEntry, password: String) {
+    if entry.password != password {
+        entry.password_history.push(PasswordHistoryItem {
+            password: std::mem::take(&mut entry.password),
+            replaced_at: chrono::Utc::now().to_rfc3339(),
+        });
+        entry.password_changed_at = Some(chrono::Utc::now().to_rfc3339());
+    }
+
+    entry.password_strength = calculate_password_strength(&password);
+    entry.password = password;
+    entry.modified_at = chrono::Utc::now().to_rfc3339();
+}
+
+#[tauri::command]
+async fn rotate_password(
+    entry_id: u32,
+    gen_config: PasswordGenConfig,
+    master_password: Option<String>,
+) -> Result<String, String> {
+    let password = generate_from_config(&gen_config)?;
+    let mut store = load_password_store(master_password.as_deref())?;
+
+    let entry = store
+        .entries
+        .iter_mut()
+        .find(|e| e.id == entry_id)
+        .ok_or_else(|| "Entry not found".to_string())?;
+    replace_password(entry, password.clone());
+
+    save_password_store(&store, master_password.as_deref())?;
+    Ok(password)
+}
+
+#[tauri::command]
+async fn update_entry(
+    id: u32,
+    title: String,
+    username: String,
+    password: String,
+    url: Option<String>,
+    notes: Option<String>,
+    color: Option<String>,
+    glyph: Option<String>,
+    totp: Option<TotpSettings>,
+    master_password: Option<String>,
+) -> Result<(), String> {
+    let color = validate_color(color)?;
+    let glyph = validate_glyph(glyph)?;
+    let totp = totp.map(validate_totp_settings).transpose()?;
+    let mut store = load_password_store(master_password.as_deref())?;
+
+    if let Some(entry) = store.entries.iter_mut().find(|e| e.id == id) {
+        replace_password(entry, password);
+        entry.title = title;
+        entry.username = username;
+        entry.url = normalize_optional_url(url);
+        // Sensitive notes reach the list masked; saving the mask back keeps the real notes
+        if !(entry.notes_sensitive && notes.as_deref() == Some(MASKED_VALUE)) {
+            entry.notes = notes;
+        }
+        entry.color = color;
+        entry.glyph = glyph;
+        match totp {
+            Some(totp) => totp.apply_to(entry),
+            None => entry.totp_secret = None,
+        }
+
+        save_password_store(&store, master_password.as_deref())?;
+        Ok(())
+    } else {
+        Err("Entry not found".to_string())
+    }
+}
+
+fn normalize_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err("Tag must not be empty".to_string());
+    }
+    Ok(tag.to_string())
+}
+
+fn has_tag(entry: &PasswordEntry, tag: &str) -> bool {
+    entry.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+}
+
+// Tags aren't a password change, so modified_at is left alone
+#[tauri::command]
+async fn bulk_add_tag(
+    entry_ids: Vec<u32>,
+    tag: String,
+    master_password: Option<String>,
+) -> Result<usize, String> {
+    let tag = normalize_tag(&tag)?;
+    let ids: std::collections::HashSet<u32> = entry_ids.into_iter().collect();
+    let mut store = load_password_store(master_password.as_deref())?;
+
+    let mut changed = 0;
+    for entry in store.entries.iter_mut().filter(|e| ids.contains(&e.id)) {
+        if !has_tag(entry, &tag) {
+            entry.tags.push(tag.clone());
+            changed += 1;
+        }
+    }
+
+    if changed > 0 {
+        save_password_store(&store, master_password.as_deref())?;
+    }
+    Ok(changed)
+}
+
+#[tauri::command]
+async fn bulk_remove_tag(
+    entry_ids: Vec<u32>,
+    tag: String,
+    master_password: Option<String>,
+) -> Result<usize, String> {
+    let tag = normalize_tag(&tag)?;
+    let ids: std::collections::HashSet<u32> = entry_ids.into_iter().collect();
+    let mut store = load_password_store(master_password.as_deref())?;
+
+    let mut changed = 0;
+    for entry in store.entries.iter_mut().filter(|e| ids.contains(&e.id)) {
+        if has_tag(entry, &tag) {
+            entry.tags.retain(|t| !t.eq_ignore_ascii_case(&tag));
+            changed += 1;
+        }
+    }
+
+    if changed > 0 {
+        save_password_store(&store, master_password.as_deref())?;
+    }
+    Ok(changed)
+}
+
+// Trimmed names, non-empty and unique ignoring case
+fn normalize_custom_fields(custom_fields: Vec<CustomField>) -> Result<Vec<CustomField>, String> {
+    let mut seen = std::collections::HashSet::new();
+    custom_fields
+        .into_iter()
+        .map(|field| {
+            let name = field.name.trim().to_string();
+            if name.is_empty() {
+                return Err("Custom field names must not be empty".to_string());
+            }
+            if !seen.insert(name.to_lowercase()) {
+                return Err(format!("Duplicate custom field '{}'", name));
+            }
+            Ok(CustomField { name, ..field })
+        })
+        .collect()
+}
+
+#[tauri::command]
+async fn set_custom_fields(
+    entry_id: u32,
+    custom_fields: Vec<CustomField>,
+    master_password: Option<String>,
+) -> Result<(), String> {
+    let custom_fields = normalize_custom_fields(custom_fields)?;
+
+    let mut store = load_password_store(master_password.as_deref())?;
+    let entry = store
+        .entries
+        .iter_mut()
+        .find(|e| e.id == entry_id)
+        .ok_or_else(|| "Entry not found".to_string())?;
+    entry.custom_fields = custom_fields;
+
+    save_password_store(&store, master_password.as_deref())
+}
+
+fn set_archived(id: u32, archived: bool, master_password: Option<&str>) -> Result<(), String> {
+    let mut store = load_password_store(master_password)?;
+    let entry = store
+        .entries
+        .iter_mut()
+        .find(|e| e.id == id)
+        .ok_or_else(|| "Entry not found".to_string())?;
+
+    if entry.archived != archived {
+        entry.archived = archived;
+        save_password_store(&store, master_password)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn archive_entry(id: u32, master_password: Option<String>) -> Result<(), String> {
+    set_archived(id, true, master_password.as_deref())
+}
+
+#[tauri::command]
+async fn unarchive_entry(id: u32, master_password: Option<String>) -> Result<(), String> {
+    set_archived(id, false, master_password.as_deref())
+}
+
+#[derive(serde::Serialize)]
+struct TitleDuplicate {
+    id: u32,
+    username: String,
+    url: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct DuplicateTitleGroup {
+    title: String,
+    entries: Vec<TitleDuplicate>,
+}
+
+// Groups of entries sharing a title (case-insensitive), largest first
+#[tauri::command]
+async fn find_duplicate_titles(master_password: Option<String>) -> Result<Vec<DuplicateTitleGroup>, String> {
+    let store = load_password_store(master_password.as_deref())?;
+
+    let mut groups: HashMap<String, DuplicateTitleGroup> = HashMap::new();
+    for entry in store.entries {
+        let group = groups
+            .entry(entry.title.trim().to_lowercase())
+            .or_insert_with(|| DuplicateTitleGroup {
+                title: entry.title.clone(),
+                entries: Vec::new(),
+            });
+        group.entries.push(TitleDuplicate {
+            id: entry.id,
+            username: entry.username,
+            url: entry.url,
+        });
+    }
+
+    let mut duplicates: Vec<DuplicateTitleGroup> = groups
+        .into_values()
+        .filter(|group| group.entries.len() > 1)
+        .collect();
+    duplicates.sort_by(|a, b| {
+        b.entries
+            .len()
+            .cmp(&a.entries.len())
+            .then_with(|| a.title.to_lowercase().cmp(&b.title.to_lowercase()))
+    });
+
+    Ok(duplicates)
+}
+
+#[derive(serde::Serialize)]
+struct WhitespaceIssue {
+    id: u32,
+    title: String,
+    fields: Vec<&'static str>,
+}
+
+// Entries whose username or password starts or ends with whitespace
+#[tauri::command]
+async fn detect_whitespace_issues(master_password: Option<String>) -> Result<Vec<WhitespaceIssue>, String> {
+    let store = load_password_store(master_password.as_deref())?;
+    let has_padding = |value: &str| value.trim() != value;
+
+    Ok(store
+        .entries
+        .into_iter()
+        .filter_map(|entry| {
+            let mut fields = Vec::new();
+            if has_padding(&entry.username) {
+                fields.push("username");
+            }
+            if has_padding(&entry.password) {
+                fields.push("password");
+            }
+
+            (!fields.is_empty()).then(|| WhitespaceIssue {
+                id: entry.id,
+                title: entry.title,
+                fields,
+            })
+        })
+        .collect())
+}
+
+// Renumber entries 1..=n in id order; returns old -> new so callers can remap
+#[tauri::command]
+async fn compact_ids(
+    master_password: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<HashMap<u32, u32>, String> {
+    let mut store = load_password_store(master_password.as_deref())?;
+    store.entries.sort_by_key(|entry| entry.id);
+
+    // Hotkeys left behind by entries that no longer exist would otherwise be
+    // remapped onto whichever entry inherits their id
+    let known: std::collections::HashSet<u32> = store.entries.iter().map(|entry| entry.id).collect();
+    release_entry_hotkeys(&app_handle, |id| known.contains(&id))?;
+
+    let mut mapping = HashMap::new();
+    for (new_id, entry) in (1u32..).zip(store.entries.iter_mut()) {
+        if entry.id != new_id {
+            mapping.insert(entry.id, new_id);
+            entry.id = new_id;
+        }
+    }
+    store.next_id = store.entries.len() as u32 + 1;
+
+    save_password_store(&store, master_password.as_deref())?;
+
+    if !mapping.is_empty() {
+        PENDING_PASSWORD_STEPS.lock().unwrap().clear();
+
+        for entry_id in ENTRY_HOTKEYS.lock().unwrap().values_mut() {
+            if let Some(new_id) = mapping.get(entry_id) {
+                *entry_id = *new_id;
+            }
+        }
+
+        let mut settings = load_settings()?;
+        settings.entry_hotkeys = settings
+            .entry_hotkeys
+            .into_iter()
+            .map(|(entry_id, shortcut)| (mapping.get(&entry_id).copied().unwrap_or(entry_id), shortcut))
+            .collect();
+        save_settings(&settings)?;
+    }
+
+    Ok(mapping)
+}
+
+#[tauri::command]
+async fn prune_password_history(older_than_days: u32, master_password: Option<String>) -> Result<usize, String> {
+    let mut store = load_password_store(master_password.as_deref())?;
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(i64::from(older_than_days));
+
+    let mut pruned = 0;
+    for entry in &mut store.entries {
+        let before = entry.password_history.len();
+        // Keep items with an unparseable timestamp rather than guess their age
+        entry.password_history.retain(|item| {
+            chrono::DateTime::parse_from_rfc3339(&item.replaced_at)
+                .map(|replaced_at| replaced_at >= cutoff)
+                .unwrap_or(true)
+        });
+        pruned += before - entry.password_history.len();
+    }
+
+    if pruned > 0 {
+        save_password_store(&store, master_password.as_deref())?;
+    }
+
+    Ok(pruned)
+}
+
+#[tauri::command]
+async fn delete_entry(
+    id: u32,
+    master_password: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let mut store = load_password_store(master_password.as_deref())?;
+
+    if let Some(pos) = store.entries.iter().position(|e| e.id == id) {
+        store.entries.remove(pos);
+        save_password_store(&store, master_password.as_deref())?;
+        release_entry_hotkeys(&app_handle, |entry_id| entry_id != id)
+    } else {
+        Err("Entry not found".to_string())
+    }
+}
+
+// Folds `secondary` into `primary` and deletes it. The primary keeps its
+// password; the secondary's becomes part of the combined history.
+#[tauri::command]
+async fn merge_entries(
+    primary_id: u32,
+    secondary_id: u32,
+    master_password: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<PasswordEntry, String> {
+    if primary_id == secondary_id {
+        return Err("Cannot merge an entry with itself".to_string());
+    }
+
+    let mut store = load_password_store(master_password.as_deref())?;
+    let secondary_pos = store
+        .entries
+        .iter()
+        .position(|e| e.id == secondary_id)
+        .ok_or_else(|| format!("Entry {} not found", secondary_id))?;
+    if !store.entries.iter().any(|e| e.id == primary_id) {
+        return Err(format!("Entry {} not found", primary_id));
+    }
+    let secondary = store.entries.remove(secondary_pos);
+    let primary = store
+        .entries
+        .iter_mut()
+        .find(|e| e.id == primary_id)
+        .ok_or_else(|| format!("Entry {} not found", primary_id))?;
+
+    primary.password_history.extend(secondary.password_history);
+    if !secondary.password.is_empty() && secondary.password != primary.password {
+        primary.password_history.push(PasswordHistoryItem {
+            password: secondary.password,
+            replaced_at: chrono::Utc::now().to_rfc3339(),
+        });
+    }
+    // RFC 3339 timestamps sort chronologically; keep oldest first
+    primary
+        .password_history
+        .sort_by(|a, b| a.replaced_at.cmp(&b.replaced_at));
+
+    primary.notes = match (primary.notes.take(), secondary.notes) {
+        (Some(ours), Some(theirs)) if ours.trim() != theirs.trim() => {
+            Some(format!("{}\n\n{}", ours, theirs))
+        }
+        (ours, theirs) => ours.or(theirs),
+    };
+    primary.notes_sensitive |= secondary.notes_sensitive;
+
+    for tag in secondary.tags {
+        if !has_tag(primary, &tag) {
+            primary.tags.push(tag);
+        }
+    }
+
+    for field in secondary.custom_fields {
+        if !primary.custom_fields.iter().any(|f| f.name == field.name) {
+            primary.custom_fields.push(field);
+        }
+    }
+
+    if primary.username.is_empty() {
+        primary.username = secondary.username;
+    }
+    if primary.url.is_none() {
+        primary.url = secondary.url;
+    }
+    if primary.totp_secret.is_none() && secondary.totp_secret.is_some() {
+        primary.totp_secret = secondary.totp_secret;
+        primary.totp_digits = secondary.totp_digits;
+        primary.totp_period = secondary.totp_period;
+        primary.totp_algorithm = secondary.totp_algorithm;
+    }
+    if secondary.created_at < primary.created_at {
+        primary.created_at = secondary.created_at;
+    }
+
+    let merged = primary.clone();
+    save_password_store(&store, master_password.as_deref())?;
+    release_entry_hotkeys(&app_handle, |id| id != secondary_id)?;
+
+    Ok(merged)
+}
+
+// Backups from before a rekey carry their own salt and KDF cost, so they
+// can't reuse the live session key
+fn backup_store_key(backup: &EncryptedPasswordStore, master_password: Option<&str>) -> Result<Vec<u8>, String> {
+    let live_salt = load_encrypted_store().ok().map(|s| s.salt);
+    if live_salt.as_deref() == Some(backup.salt.as_str()) {
+        return vault_key(master_password);
+    }
+
+    let Some(master_password) = master_password else {
+        return Err("Enter your master password to open this backup".to_string());
+    };
+    derive_master_key(master_password)?;
+
+    let salt = general_purpose::STANDARD
+        .decode(&backup.salt)
+        .map_err(|e| format!("Failed to decode backup salt: {}", e))?;
+    let key = generate_key_with_params(master_password, &salt, header_kdf_params(backup)?)?;
+    apply_keyfile_check(key, backup.keyfile_check.as_deref())
+}
+
+fn load_backup_store(backup_filename: &str, master_password: Option<&str>) -> Result<PasswordStore, String> {
+    let content = fs::read_to_string(get_backup_path(backup_filename)?)
+        .map_err(|e| format!("Failed to read backup: {}", e))?;
+    let backup: EncryptedPasswordStore =
+        serde_json::from_str(&content).map_err(|e| format!("Backup is corrupt: {}", e))?;
+
+    if backup.version > VAULT_FORMAT_VERSION {
+        return Err(NEWER_VAULT_ERROR.to_string());
+    }
+
+    let key = zeroize::Zeroizing::new(backup_store_key(&backup, master_password)?);
+    let plaintext = decrypt_bytes(&backup.encrypted_data, &backup.nonce, &key)
+        .map_err(|_| "Backup could not be decrypted with this master password".to_string())?;
+    let decrypted_data = unseal_store_json(plaintext, backup.compressed)
+        .map_err(|e| format!("Backup is corrupt: {}", e))?;
+
+    serde_json::from_str(&decrypted_data).map_err(|e| format!("Backup is corrupt: {}", e))
+}
+
+// Copies the live vault file as-is into backups/ and returns the new file name
+fn backup_current_vault() -> Result<String, String> {
+    let backup_filename = format!(
+        "vault-{}.cocoon",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S%.3f")
+    );
+    fs::copy(get_data_file_path()?, get_backup_dir()?.join(&backup_filename))
+        .map_err(|e| format!("Failed to back up vault: {}", e))?;
+
+    Ok(backup_filename)
+}
+
+#[tauri::command]
+async fn create_backup() -> Result<String, String> {
+    backup_current_vault()
+}
+
+#[derive(serde::Serialize)]
+struct FormatUpgrade {
+    old_version: u8,
+    new_version: u8,
+    backup: String,
+}
+
+// Decrypts an encrypted store held in memory and checks it yields exactly the
+// expected JSON
+fn reopens_as(encrypted_store: &EncryptedPasswordStore, key: &[u8], expected_json: &str) -> Result<(), String> {
+    let plaintext = decrypt_bytes(&encrypted_store.encrypted_data, &encrypted_store.nonce, key)?;
+    let store_json = zeroize::Zeroizing::new(unseal_store_json(plaintext, encrypted_store.compressed)?);
+    serde_json::from_str::<PasswordStore>(&store_json)
+        .map_err(|e| format!("Rewritten vault could not be parsed: {}", e))?;
+
+    if store_json.as_str() != expected_json {
+        return Err("Rewritten vault does not match the original contents".to_string());
+    }
+    Ok(())
+}
+
+// Re-encrypts the vault in `target_version` (also picking up the current
+// compression setting). The new file is checked in memory before it replaces
+// the old one and again after it's on disk; a failure restores the backup.
+//
+// The vault key stays the same: it is derived under master.hash's Argon2
+// parameters, so a format that needs a new KDF has to go through rekey_vault
+// (rekey_with_session, or the automatic upgrade on unlock) rather than here.
+// Format 1 is the only one today, so no target needs that yet.
+#[tauri::command]
+async fn upgrade_vault_format(target_version: u8, master_password: Option<String>) -> Result<FormatUpgrade, String> {
+    if target_version == 0 || target_version > VAULT_FORMAT_VERSION {
+        return Err(format!(
+            "Vault format {} is not supported by this version of Cocoon",
+            target_version
+        ));
+    }
+
+    let key = zeroize::Zeroizing::new(vault_key(master_password.as_deref())?);
+    let (mut encrypted_store, content) = load_encrypted_store_with_content()?;
+    check_vault_unchanged(&encrypted_store, &content)?;
+    remember_vault_fingerprint(&content);
+    let old_version = encrypted_store.version;
+    if target_version < old_version {
+        return Err(format!(
+            "Cannot downgrade the vault from format {} to {}",
+            old_version, target_version
+        ));
+    }
+
+    let plaintext = decrypt_bytes(&encrypted_store.encrypted_data, &encrypted_store.nonce, &key)
+        .map_err(|_| WRONG_KEY_ERROR.to_string())?;
+    let original_json = zeroize::Zeroizing::new(unseal_store_json(plaintext, encrypted_store.compressed)?);
+    let store: PasswordStore = serde_json::from_str(&original_json)
+        .map_err(|e| format!("Failed to parse vault: {}", e))?;
+    let store_json = zeroize::Zeroizing::new(
+        serde_json::to_string(&store).map_err(|e| format!("Failed to serialize store: {}", e))?,
+    );
+
+    let (encrypted_data, nonce, compressed) = seal_store_json(&store_json, &key)?;
+    encrypted_store.encrypted_data = encrypted_data;
+    encrypted_store.nonce = nonce;
+    encrypted_store.compressed = compressed;
+    encrypted_store.version = target_version;
+    encrypted_store.revision += 1;
+    reopens_as(&encrypted_store, &key, &store_json)?;
+
+    // Another process may have saved while we were re-encrypting
+    let (current, content) = load_encrypted_store_with_content()?;
+    check_vault_unchanged(&current, &content)?;
+
+    let backup = backup_current_vault()?;
+    save_encrypted_store(&encrypted_store)?;
+
+    let on_disk = load_encrypted_store().and_then(|written| reopens_as(&written, &key, &store_json));
+    if let Err(e) = on_disk {
+        tracing::error!("Vault format upgrade failed verification, restoring backup: {}", e);
+        let backup_path = get_backup_dir()?.join(&backup);
+        let content = fs::read(&backup_path).map_err(|e| format!("Failed to read backup {}: {}", backup, e))?;
+        write_atomically(&get_data_file_path()?, &content)?;
+        remember_vault_fingerprint(&String::from_utf8_lossy(&content));
+        return Err(format!("Vault format upgrade failed and was rolled back: {}", e));
+    }
+
+    tracing::info!(old_version, target_version, "Vault format upgraded");
+    Ok(FormatUpgrade {
+        old_version,
+        new_version: target_version,
+        backup,
+    })
+}
+
+#[derive(serde::Serialize)]
+struct VaultDiagnostics {
+    vault_path: String,
+    vault_size_bytes: Option<u64>,
+    vault_modified_at: Option<String>,
+    master_hash_present: bool,
+    format_version: Option<u8>,
+    iterations: Option<u32>,
+    compressed: Option<bool>,
+    backup_count: usize,
+    backup_total_bytes: u64,
+}
+
+// Header and file metadata only; never decrypts anything
+#[tauri::command]
+async fn vault_diagnostics() -> Result<VaultDiagnostics, String> {
+    let vault_path = get_data_file_path()?;
+    let metadata = fs::metadata(&vault_path).ok();
+    let header = load_encrypted_store().ok();
+
+    let mut backup_count = 0;
+    let mut backup_total_bytes = 0;
+    if let Ok(backups) = fs::read_dir(get_backup_dir()?) {
+        for backup in backups.flatten() {
+            match backup.metadata() {
+                Ok(metadata) if metadata.is_file() => {
+                    backup_count += 1;
+                    backup_total_bytes += metadata.len();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(VaultDiagnostics {
+        vault_path: vault_path.display().to_string(),
+        vault_size_bytes: metadata.as_ref().map(|m| m.len()),
+        vault_modified_at: metadata
+            .and_then(|m| m.modified().ok())
+            .map(|modified| chrono::DateTime::<chrono::Utc>::from(modified).to_rfc3339()),
+        master_hash_present: get_master_hash_path()?.exists(),
+        format_version: header.as_ref().map(|h| h.version),
+        iterations: header.as_ref().map(|h| h.iterations),
+        compressed: header.as_ref().map(|h| h.compressed),
+        backup_count,
+        backup_total_bytes,
+    })
+}
+
+// Salt and KDF cost recorded in master.hash, salt encoded the way the vault
+// header stores it
+fn master_hash_salt() -> Result<(String, argon2::Params), String> {
+    let stored_hash = fs::read_to_string(get_master_hash_path()?)
+        .map_err(|e| format!("Failed to read master password hash: {}", e))?;
+    let parsed_hash = PasswordHash::new(&stored_hash)
+        .map_err(|e| format!("Failed to parse password hash: {}", e))?;
+    let salt = parsed_hash
+        .salt
+        .ok_or_else(|| "Master password hash has no salt".to_string())?;
+    let params = argon2::Params::try_from(&parsed_hash)
+        .map_err(|e| format!("Failed to read KDF parameters: {}", e))?;
+
+    Ok((general_purpose::STANDARD.encode(salt.as_str().as_bytes()), params))
+}
+
+// Legacy vaults can carry a header salt that differs from master.hash. The key
+// is always derived from master.hash, so either the header is just stale (fix
+// the metadata) or the data was sealed under the header's salt (re-encrypt).
+#[tauri::command]
+async fn repair_vault(master_password: String) -> Result<String, String> {
+    if master_password.is_empty() {
+        return Err("Enter your master password to repair the vault".to_string());
+    }
+
+    // Deriving the key can upgrade the KDF, which rewrites master.hash and the
+    // vault, so both are read only afterwards
+    let key = zeroize::Zeroizing::new(vault_key(Some(&master_password))?);
+    let (expected_salt, params) = master_hash_salt()?;
+    let (mut encrypted_store, content) = load_encrypted_store_with_content()?;
+    check_vault_unchanged(&encrypted_store, &content)?;
+    remember_vault_fingerprint(&content);
+    if encrypted_store.salt == expected_salt {
+        return Ok("already consistent".to_string());
+    }
+
+    let opens_with_current_key =
+        decrypt_bytes(&encrypted_store.encrypted_data, &encrypted_store.nonce, &key).is_ok();
+
+    let outcome = if opens_with_current_key {
+        "repaired vault metadata"
+    } else {
+        let legacy_salt = general_purpose::STANDARD
+            .decode(&encrypted_store.salt)
+            .map_err(|e| format!("Failed to decode vault salt: {}", e))?;
+        let legacy_key = zeroize::Zeroizing::new(apply_keyfile(generate_key_with_params(
+            &master_password,
+            &legacy_salt,
+            header_kdf_params(&encrypted_store)?,
+        )?)?);
+        let plaintext = decrypt_bytes(
+            &encrypted_store.encrypted_data,
+            &encrypted_store.nonce,
+            &legacy_key,
+        )
+        .map_err(|_| "Vault could not be opened with either salt; nothing was changed".to_string())?;
+        let store_json =
+            zeroize::Zeroizing::new(unseal_store_json(plaintext, encrypted_store.compressed)?);
+
+        let (encrypted_data, nonce, compressed) = seal_store_json(&store_json, &key)?;
+        encrypted_store.encrypted_data = encrypted_data;
+        encrypted_store.nonce = nonce;
+        encrypted_store.compressed = compressed;
+        encrypted_store.revision += 1;
+        "re-encrypted vault with the master password salt"
+    };
+
+    // Another process may have saved while we were re-encrypting
+    let (current, content) = load_encrypted_store_with_content()?;
+    check_vault_unchanged(&current, &content)?;
+
+    backup_current_vault()?;
+    encrypted_store.salt = expected_salt;
+    encrypted_store.iterations = params.t_cost();
+    encrypted_store.kdf_params = Some(kdf_params_header(&params));
+    save_encrypted_store(&encrypted_store)?;
+    UNLOCKED_STORE.lock().unwrap().take();
+
+    tracing::info!(outcome, "Vault repaired");
+    Ok(outcome.to_string())
+}
+
+#[derive(serde::Serialize)]
+struct CryptoInfo {
+    kdf: String,
+    kdf_version: Option<u32>,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    cipher: &'static str,
+    nonce_bytes: usize,
+    key_bytes: usize,
+    format_version: Option<u8>,
+    compressed: Option<bool>,
+    keyfile_required: bool,
+}
+
+// KDF settings come from the PHC string in master.hash (the same parameters
+// the vault key is derived with); nothing secret is returned
+#[tauri::command]
+async fn crypto_info() -> Result<CryptoInfo, String> {
+    let stored_hash = fs::read_to_string(get_master_hash_path()?)
+        .map_err(|e| format!("Failed to read master password hash: {}", e))?;
+    let parsed_hash = PasswordHash::new(&stored_hash)
+        .map_err(|e| format!("Failed to parse password hash: {}", e))?;
+    let params = argon2::Params::try_from(&parsed_hash)
+        .map_err(|e| format!("Failed to read KDF parameters: {}", e))?;
+    let header = load_encrypted_store().ok();
+
+    Ok(CryptoInfo {
+        kdf: parsed_hash.algorithm.to_string(),
+        kdf_version: parsed_hash.version,
+        memory_kib: params.m_cost(),
+        iterations: params.t_cost(),
+        parallelism: params.p_cost(),
+        cipher: "AES-256-GCM",
+        nonce_bytes: 12,
+        key_bytes: 32,
+        format_version: header.as_ref().map(|h| h.version),
+        compressed: header.as_ref().map(|h| h.compressed),
+        keyfile_required: header.is_some_and(|h| h.keyfile_check.is_some()),
+    })
+}
+
+#[derive(serde::Serialize)]
+struct BackupInfo {
+    entry_count: usize,
+    created_at: String,
+}
+
+// Decrypts and parses a backup without touching the live vault
+#[tauri::command]
+async fn verify_backup(backup_filename: String, master_password: Option<String>) -> Result<BackupInfo, String> {
+    let store = load_backup_store(&backup_filename, master_password.as_deref())?;
+
+    Ok(BackupInfo {
+        entry_count: store.entries.len(),
+        created_at: store.created_at,
+    })
+}
+
+// The current vault is backed up first; the restored entries are re-encrypted
+// under the current key so the vault keeps matching master.hash
+#[tauri::command]
+async fn restore_backup(backup_filename: String, master_password: Option<String>) -> Result<BackupInfo, String> {
+    let store = load_backup_store(&backup_filename, master_password.as_deref())?;
+
+    let safety_backup = backup_current_vault()?;
+    tracing::info!(safety_backup = %safety_backup, "Backed up vault before restore");
+
+    save_password_store(&store, master_password.as_deref())?;
+
+    Ok(BackupInfo {
+        entry_count: store.entries.len(),
+        created_at: store.created_at,
+    })
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+enum SortOrder {
+    #[default]
+    Recent,
+    Title,
+    // sort_index ascending; entries never reordered follow in title order
+    Manual,
+}
+
+fn sort_entries(entries: &mut [&PasswordEntry], order: SortOrder) {
+    match order {
+        SortOrder::Recent => entries.sort_by(|a, b| b.modified_at.cmp(&a.modified_at)),
+        SortOrder::Title => entries.sort_by_key(|entry| entry.title.to_lowercase()),
+        SortOrder::Manual => entries.sort_by_key(|entry| {
+            (
+                entry.sort_index.is_none(),
+                entry.sort_index,
+                entry.title.to_lowercase(),
+            )
+        }),
+    }
+}
+
+#[tauri::command]
+async fn reorder_entries(ordered_ids: Vec<u32>, master_password: Option<String>) -> Result<(), String> {
+    let mut store = load_password_store(master_password.as_deref())?;
+
+    let positions: HashMap<u32, i32> = ordered_ids
+        .iter()
+        .enumerate()
+        .map(|(index, id)| (*id, index as i32))
+        .collect();
+    if positions.len() != ordered_ids.len() {
+        return Err("Entry ids must not repeat".to_string());
+    }
+    if let Some(id) = ordered_ids
+        .iter()
+        .find(|id| !store.entries.iter().any(|e| e.id == **id))
+    {
+        return Err(format!("Entry {} not found", id));
+    }
+
+    // Entries left out of the list drop back to the unordered tail
+    for entry in store.entries.iter_mut() {
+        entry.sort_index = positions.get(&entry.id).copied();
+    }
+
+    save_password_store(&store, master_password.as_deref())
+}
+
+// List row without any secrets; fetch the full entry with get_entry_by_id
+#[derive(serde::Serialize)]
+struct EntrySummary {
+    id: u32,
+    title: String,
+    username: String,
+    url: Option<String>,
+    modified_at: String,
+    password_strength: u8,
+    color: Option<String>,
+    glyph: Option<String>,
+    custom_fields: Vec<CustomField>,
+    archived: bool,
+}
+
+fn entry_summary(entry: &PasswordEntry) -> EntrySummary {
+    EntrySummary {
+        id: entry.id,
+        title: entry.title.clone(),
+        username: entry.username.clone(),
+        url: entry.url.clone(),
+        modified_at: entry.modified_at.clone(),
+        password_strength: entry.password_strength,
+        color: entry.color.clone(),
+        glyph: entry.glyph.clone(),
+        custom_fields: custom_field_previews(&entry.custom_fields),
+        archived: entry.archived,
+    }
+}
+
+#[derive(serde::Serialize)]
+struct EntryPage {
+    entries: Vec<EntrySummary>,
+    total: usize,
+}
+
+#[tauri::command]
+async fn list_entries_paged(
+    offset: usize,
+    limit: usize,
+    sort: Option<SortOrder>,
+    include_archived: Option<bool>,
+    master_password: Option<String>,
+) -> Result<EntryPage, String> {
+    let include_archived = include_archived.unwrap_or(false);
+
+    with_entries(master_password.as_deref(), |entries| {
+        let mut entries: Vec<&PasswordEntry> = entries
+            .iter()
+            .copied()
+            .filter(|entry| include_archived || !entry.archived)
+            .collect();
+        sort_entries(&mut entries, sort.unwrap_or_default());
+
+        let total = entries.len();
+        let entries = entries
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(entry_summary)
+            .collect();
+
+        EntryPage { entries, total }
+    })
+}
+
+#[tauri::command]
+async fn get_entry_by_id(id: u32, master_password: Option<String>) -> Result<EntryDetails, String> {
+    let mut entry = find_entry(id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+
+    let notes_token = if entry.notes_sensitive {
+        entry.notes.take().map(store_reveal)
+    } else {
+        None
+    };
+
+    Ok(EntryDetails { entry, notes_token })
+}
+
+#[derive(serde::Serialize)]
+struct EntryDetails {
+    #[serde(flatten)]
+    entry: PasswordEntry,
+    // Set instead of `notes` for sensitive notes; redeem with reveal_notes
+    notes_token: Option<String>,
+}
+
+#[tauri::command]
+async fn set_notes_sensitive(
+    entry_id: u32,
+    sensitive: bool,
+    master_password: Option<String>,
+) -> Result<(), String> {
+    let mut store = load_password_store(master_password.as_deref())?;
+    let entry = store
+        .entries
+        .iter_mut()
+        .find(|e| e.id == entry_id)
+        .ok_or_else(|| "Entry not found".to_string())?;
+    entry.notes_sensitive = sensitive;
+
+    save_password_store(&store, master_password.as_deref())
+}
+
+#[tauri::command]
+async fn add_passkey(
+    title: String,
+    username: String,
+    url: Option<String>,
+    notes: Option<String>,
+    rp_id: String,
+    user_handle: String,
+    credential_id: String,
+    private_key: String,
+    master_password: Option<String>,
+) -> Result<u32, String> {
+    if rp_id.trim().is_empty() {
+        return Err("Relying party id is required".to_string());
+    }
+    if credential_id.trim().is_empty() || private_key.trim().is_empty() {
+        return Err("Credential id and private key are required".to_string());
+    }
+
+    let mut store = load_password_store(master_password.as_deref())?;
+
+    let entry = PasswordEntry {
+        id: store.next_id,
+        title,
+        username,
+        password: String::new(),
+        url,
+        notes,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        modified_at: chrono::Utc::now().to_rfc3339(),
+        password_strength: 0,
+        kind: EntryKind::Passkey(PasskeyCredential {
+            rp_id,
+            user_handle,
+            credential_id,
+            private_key,
+        }),
+        ..Default::default()
+    };
+
+    let entry_id = entry.id;
+    store.entries.push(entry);
+    store.next_id += 1;
+
+    save_password_store(&store, master_password.as_deref())?;
+
+    Ok(entry_id)
+}
+
+#[tauri::command]
+async fn get_passkey(id: u32, master_password: Option<String>) -> Result<PasskeyCredential, String> {
+    let entry = find_entry(id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+
+    match entry.kind {
+        EntryKind::Passkey(passkey) => Ok(passkey),
+        EntryKind::Login => Err("Entry is not a passkey".to_string()),
+    }
+}
+
+// Re-type-to-confirm check; constant time so it can't be used as a guessing oracle
+#[tauri::command]
+async fn confirm_password_matches(
+    entry_id: u32,
+    candidate: String,
+    master_password: Option<String>,
+) -> Result<bool, String> {
+    use subtle::ConstantTimeEq;
+
+    let entry =
+        find_entry(entry_id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+
+    Ok(entry
+        .password
+        .as_bytes()
+        .ct_eq(candidate.as_bytes())
+        .into())
+}
+
+// Spotlight panel geometry (logical pixels)
+const PANEL_BASE_HEIGHT: f64 = 72.0;
+const PANEL_ROW_HEIGHT: f64 = 44.0;
+const PANEL_MAX_ROWS: usize = 8;
+
+// Grow/shrink the panel with the result list, keeping its top edge where it is
+#[tauri::command]
+async fn resize_for_results(count: usize, app_handle: tauri::AppHandle) -> Result<(), String> {
+    let window = app_handle
+        .get_webview_window("main")
+        .ok_or("Main window not found")?;
+
+    let scale_factor = window
+        .scale_factor()
+        .map_err(|e| format!("Failed to read scale factor: {}", e))?;
+    let size = window
+        .outer_size()
+        .map_err(|e| format!("Failed to read window size: {}", e))?;
+    let position = window
+        .outer_position()
+        .map_err(|e| format!("Failed to read window position: {}", e))?;
+
+    let width = size.width as f64 / scale_factor;
+    let height = PANEL_BASE_HEIGHT + PANEL_ROW_HEIGHT * count.min(PANEL_MAX_ROWS) as f64;
+
+    window
+        .set_size(tauri::LogicalSize::new(width, height))
+        .map_err(|e| format!("Failed to resize window: {}", e))?;
+
+    if let Some(monitor) = window
+        .current_monitor()
+        .map_err(|e| format!("Failed to read current monitor: {}", e))?
+    {
+        let x = monitor.position().x + (monitor.size().width as i32 - size.width as i32) / 2;
+        window
+            .set_position(tauri::PhysicalPosition::new(x, position.y))
+            .map_err(|e| format!("Failed to reposition window: {}", e))?;
+    }
+
+    Ok(())
+}
+
+// Plaintext handed out via one-shot tokens instead of living in the JS heap
+struct RevealSlot {
+    value: zeroize::Zeroizing<String>,
+    expires_at: std::time::Instant,
+}
+
+const REVEAL_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
+lazy_static::lazy_static! {
+    static ref REVEAL_SLOTS: Mutex<HashMap<String, RevealSlot>> = Mutex::new(HashMap::new());
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn store_reveal(value: String) -> String {
+    let token = generate_token();
+    let mut slots = REVEAL_SLOTS.lock().unwrap();
+
+    let now = std::time::Instant::now();
+    slots.retain(|_, slot| slot.expires_at > now);
+    slots.insert(
+        token.clone(),
+        RevealSlot {
+            value: zeroize::Zeroizing::new(value),
+            expires_at: now + REVEAL_TTL,
+        },
+    );
+
+    token
+}
+
+#[tauri::command]
+async fn request_reveal(entry_id: u32, master_password: Option<String>) -> Result<String, String> {
+    let entry =
+        find_entry(entry_id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+
+    Ok(store_reveal(entry.password))
+}
+
+const MAX_TIMED_REVEAL_MS: u64 = 60_000;
+
+// The backend owns the re-mask timer, so the password is hidden again even if
+// the UI that showed it has moved on
+#[tauri::command]
+async fn reveal_password_timed(
+    entry_id: u32,
+    master_password: Option<String>,
+    visible_ms: u64,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    let entry =
+        find_entry(entry_id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+    let visible_for = std::time::Duration::from_millis(visible_ms.min(MAX_TIMED_REVEAL_MS));
+
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(visible_for).await;
+        let _ = app_handle.emit("rehide-password", entry_id);
+    });
+
+    Ok(entry.password)
+}
+
+// Tokens are single-use and expire after REVEAL_TTL
+#[tauri::command]
+async fn consume_reveal(token: String) -> Result<String, String> {
+    let slot = REVEAL_SLOTS
+        .lock()
+        .unwrap()
+        .remove(&token)
+        .ok_or_else(|| "Reveal token is invalid or already used".to_string())?;
+
+    if slot.expires_at <= std::time::Instant::now() {
+        return Err("Reveal token has expired".to_string());
+    }
+
+    Ok(slot.value.to_string())
+}
+
+#[tauri::command]
+async fn reveal_notes(token: String) -> Result<String, String> {
+    consume_reveal(token).await
+}
+
+#[tauri::command]
+async fn set_allow_network_checks(enabled: bool) -> Result<(), String> {
+    let mut settings = load_settings()?;
+    settings.allow_network_checks = enabled;
+    save_settings(&settings)
+}
+
+const DEAD_URL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+const DEAD_URL_CONCURRENCY: usize = 8;
+
+#[derive(serde::Serialize)]
+struct DeadUrl {
+    id: u32,
+    title: String,
+    url: String,
+    reason: String,
+}
+
+// None if the site answered with a non-error status
+async fn probe_url(client: &reqwest::Client, url: &str) -> Option<String> {
+    let response = match client.head(url).send().await {
+        // Some servers don't implement HEAD; ask again with GET before judging
+        Ok(response)
+            if response.status() == reqwest::StatusCode::METHOD_NOT_ALLOWED
+                || response.status() == reqwest::StatusCode::NOT_IMPLEMENTED =>
+        {
+            client.get(url).send().await
+        }
+        other => other,
+    };
+
+    match response {
+        Ok(response) if response.status().is_client_error() || response.status().is_server_error() => {
+            Some(format!("HTTP {}", response.status().as_u16()))
+        }
+        Ok(_) => None,
+        Err(e) => Some(e.to_string()),
+    }
+}
+
+#[tauri::command]
+async fn check_dead_urls(master_password: Option<String>) -> Result<Vec<DeadUrl>, String> {
+    if !load_settings()?.allow_network_checks {
+        return Err("Network checks are disabled in settings".to_string());
+    }
+
+    let store = load_password_store(master_password.as_deref())?;
+    let client = reqwest::Client::builder()
+        .timeout(DEAD_URL_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::limited(10))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let permits = Arc::new(tokio::sync::Semaphore::new(DEAD_URL_CONCURRENCY));
+
+    let mut checks = tokio::task::JoinSet::new();
+    for entry in store.entries {
+        let url = match entry.url {
+            Some(url) if url.starts_with("http://") || url.starts_with("https://") => url,
+            _ => continue,
+        };
+        let client = client.clone();
+        let permits = Arc::clone(&permits);
+
+        checks.spawn(async move {
+            let _permit = permits.acquire_owned().await.ok()?;
+            probe_url(&client, &url).await.map(|reason| DeadUrl {
+                id: entry.id,
+                title: entry.title,
+                url,
+                reason,
+            })
+        });
+    }
+
+    let mut dead = Vec::new();
+    while let Some(result) = checks.join_next().await {
+        if let Ok(Some(dead_url)) = result {
+            dead.push(dead_url);
+        }
+    }
+    dead.sort_by_key(|d| d.id);
+
+    Ok(dead)
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+enum FieldKind {
+    Title,
+    Username,
+    Url,
+    Notes,
+}
+
+#[derive(serde::Serialize)]
+struct FieldChange {
+    id: u32,
+    before: String,
+    after: String,
+}
+
+#[derive(serde::Serialize)]
+struct FindReplaceResult {
+    changed: usize,
+    preview: Vec<FieldChange>,
+}
+
+#[tauri::command]
+async fn set_searchable_fields(fields: Vec<FieldKind>) -> Result<(), String> {
+    if fields.is_empty() {
+        return Err("At least one field must stay searchable".to_string());
+    }
+
+    let mut settings = load_settings()?;
+    settings.searchable_fields = fields.into_iter().collect();
+    save_settings(&settings)
+}
+
+// Bulk rename across one field; `dry_run` returns the preview without saving
+#[tauri::command]
+async fn find_replace(
+    field: FieldKind,
+    find: String,
+    replace: String,
+    case_sensitive: bool,
+    dry_run: bool,
+    master_password: Option<String>,
+) -> Result<FindReplaceResult, String> {
+    if find.is_empty() {
+        return Err("Search text must not be empty".to_string());
+    }
+
+    let pattern = regex::RegexBuilder::new(&regex::escape(&find))
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid search text: {}", e))?;
+
+    let mut store = load_password_store(master_password.as_deref())?;
+    let mut preview = Vec::new();
+
+    for entry in store.entries.iter_mut() {
+        let value = match field {
+            FieldKind::Title => Some(&mut entry.title),
+            FieldKind::Username => Some(&mut entry.username),
+            FieldKind::Url => entry.url.as_mut(),
+            FieldKind::Notes => entry.notes.as_mut(),
+        };
+
+        let Some(value) = value else { continue };
+        if !pattern.is_match(value) {
+            continue;
+        }
+
+        let after = pattern
+            .replace_all(value, regex::NoExpand(&replace))
+            .to_string();
+        preview.push(FieldChange {
+            id: entry.id,
+            before: std::mem::replace(value, after.clone()),
+            after,
+        });
+        entry.modified_at = chrono::Utc::now().to_rfc3339();
+    }
+
+    if !dry_run && !preview.is_empty() {
+        save_password_store(&store, master_password.as_deref())?;
+    }
+
+    Ok(FindReplaceResult {
+        changed: preview.len(),
+        preview,
+    })
+}
+
+// Badge count; without a password it reuses the unlocked session key
+#[tauri::command]
+async fn get_entry_count(master_password: Option<String>) -> Result<usize, String> {
+    with_entries(master_password.as_deref(), |entries| entries.len())
+}
+
+const WEAK_PASSWORD_THRESHOLD: u8 = 50;
+const EXPIRY_WARNING_DAYS: i64 = 14;
+
+// Sites known to offer TOTP-based 2FA, one registrable domain per line;
+// bundled so the audit works offline. Regenerate from 2fa.directory with
+// scripts/update-totp-sites.sh.
+const TOTP_SITES: &str = include_str!("../resources/totp-sites.txt");
+
+lazy_static::lazy_static! {
+    static ref TOTP_SUPPORTED_DOMAINS: std::collections::HashSet<&'static str> = TOTP_SITES
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+}
+
+fn site_supports_totp(entry: &PasswordEntry) -> bool {
+    entry
+        .url
+        .as_deref()
+        .and_then(registrable_domain)
+        .is_some_and(|domain| TOTP_SUPPORTED_DOMAINS.contains(domain.as_str()))
+}
+
+#[derive(serde::Serialize)]
+struct MissingTotp {
+    id: u32,
+    title: String,
+    domain: String,
+}
+
+// Upper bounds (exclusive) in bits; the last bucket is open-ended
+const ENTROPY_BUCKETS: &[(&str, f64)] = &[
+    ("very_weak", 28.0),
+    ("weak", 36.0),
+    ("reasonable", 60.0),
+    ("strong", 128.0),
+    ("very_strong", f64::INFINITY),
+];
+
+// Below this an entry is listed as an outlier
+const LOW_ENTROPY_BITS: f64 = 36.0;
+
+#[derive(serde::Serialize)]
+struct EntropyBucket {
+    label: &'static str,
+    count: usize,
+}
+
+#[derive(serde::Serialize)]
+struct EntropyReport {
+    count: usize,
+    min_bits: f64,
+    max_bits: f64,
+    median_bits: f64,
+    mean_bits: f64,
+    buckets: Vec<EntropyBucket>,
+    low_entropy_ids: Vec<u32>,
+}
+
+// Bits are the lower of the charset and Shannon estimates, rounded to one
+// decimal; only numbers and ids leave this function
+#[tauri::command]
+async fn password_entropy_report(master_password: Option<String>) -> Result<EntropyReport, String> {
+    let store = load_password_store(master_password.as_deref())?;
+
+    let mut scored: Vec<(u32, f64)> = store
+        .entries
+        .iter()
+        .filter(|entry| !entry.password.is_empty())
+        .map(|entry| {
+            let bits = charset_entropy_bits(&entry.password).min(shannon_entropy_bits(&entry.password));
+            (entry.id, (bits * 10.0).round() / 10.0)
+        })
+        .collect();
+    scored.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let bits: Vec<f64> = scored.iter().map(|(_, bits)| *bits).collect();
+    let median_bits = match bits.len() {
+        0 => 0.0,
+        n if n % 2 == 1 => bits[n / 2],
+        n => ((bits[n / 2 - 1] + bits[n / 2]) / 2.0 * 10.0).round() / 10.0,
+    };
+    let mean_bits = if bits.is_empty() {
+        0.0
+    } else {
+        (bits.iter().sum::<f64>() / bits.len() as f64 * 10.0).round() / 10.0
+    };
+
+    let mut lower = f64::NEG_INFINITY;
+    let buckets = ENTROPY_BUCKETS
+        .iter()
+        .map(|&(label, upper)| {
+            let count = bits.iter().filter(|&&b| b >= lower && b < upper).count();
+            lower = upper;
+            EntropyBucket { label, count }
+        })
+        .collect();
+
+    Ok(EntropyReport {
+        count: bits.len(),
+        min_bits: bits.first().copied().unwrap_or(0.0),
+        max_bits: bits.last().copied().unwrap_or(0.0),
+        median_bits,
+        mean_bits,
+        buckets,
+        low_entropy_ids: scored
+            .iter()
+            .filter(|(_, bits)| *bits < LOW_ENTROPY_BITS)
+            .map(|(id, _)| *id)
+            .collect(),
+    })
+}
+
+// Entries on sites that offer TOTP but have no secret stored
+#[tauri::command]
+async fn audit_missing_totp(master_password: Option<String>) -> Result<Vec<MissingTotp>, String> {
+    let store = load_password_store(master_password.as_deref())?;
+
+    Ok(store
+        .entries
+        .into_iter()
+        .filter(|entry| entry.totp_secret.is_none() && site_supports_totp(entry))
+        .map(|entry| MissingTotp {
+            domain: entry
+                .url
+                .as_deref()
+                .and_then(registrable_domain)
+                .unwrap_or_default(),
+            id: entry.id,
+            title: entry.title,
+        })
+        .collect())
+}
+
+#[derive(serde::Serialize, Default)]
+struct AuditSummary {
+    total: usize,
+    weak: usize,
+    reused: usize,
+    expired: usize,
+    expiring_soon: usize,
+    missing_totp: usize,
+}
+
+fn audit_store(store: &PasswordStore, max_age_days: Option<u32>) -> AuditSummary {
+    let mut summary = AuditSummary {
+        total: store.entries.len(),
+        ..AuditSummary::default()
+    };
+
+    let mut password_counts: HashMap<&str, usize> = HashMap::new();
+    for entry in store.entries.iter().filter(|e| !e.password.is_empty()) {
+        *password_counts.entry(entry.password.as_str()).or_default() += 1;
+    }
+
+    let now = chrono::Utc::now();
+    for entry in &store.entries {
+        if entry.password.is_empty() {
+            continue;
+        }
+
+        if entry.password_strength < WEAK_PASSWORD_THRESHOLD {
+            summary.weak += 1;
+        }
+        if password_counts[entry.password.as_str()] > 1 {
+            summary.reused += 1;
+        }
+        if entry.totp_secret.is_none() && site_supports_totp(entry) {
+            summary.missing_totp += 1;
+        }
+
+        // Expiry derives from the last password change and the max-age policy
+        let changed_at = chrono::DateTime::parse_from_rfc3339(password_changed_at(entry));
+        if let (Some(max_age), Ok(changed_at)) = (max_age_days, changed_at) {
+            let expires_at = changed_at.with_timezone(&chrono::Utc)
+                + chrono::Duration::days(max_age as i64);
+            if expires_at <= now {
+                summary.expired += 1;
+            } else if expires_at <= now + chrono::Duration::days(EXPIRY_WARNING_DAYS) {
+                summary.expiring_soon += 1;
+            }
+        }
+    }
+
+    summary
+}
+
+// Entries from before password_changed_at was tracked: the newest history
+// item is when the current password replaced the previous one, and with no
+// history the password is as old as the entry
+fn password_changed_at(entry: &PasswordEntry) -> &str {
+    entry
+        .password_changed_at
+        .as_deref()
+        .or_else(|| entry.password_history.last().map(|item| item.replaced_at.as_str()))
+        .unwrap_or(&entry.created_at)
+}
+
+// One decrypt for the whole post-unlock security summary
+#[tauri::command]
+async fn unlock_and_audit(master_password: String) -> Result<AuditSummary, String> {
+    unlock_with_password(&master_password)?;
+    let store = load_password_store(None)?;
+    let settings = load_settings()?;
+
+    Ok(audit_store(&store, settings.password_max_age_days))
+}
+
+// Short-lived grant for flows (like rekeying) that need the master password
+// itself, not just the vault key. Tradeoff: the password stays in memory
+// (zeroized on drop) for up to SESSION_TOKEN_TTL instead of being re-prompted.
+struct SessionGrant {
+    master_password: zeroize::Zeroizing<String>,
+    expires_at: std::time::Instant,
+}
+
+const SESSION_TOKEN_TTL: std::time::Duration = std::time::Duration::from_secs(300);
+
+lazy_static::lazy_static! {
+    static ref SESSION_GRANTS: Mutex<HashMap<String, SessionGrant>> = Mutex::new(HashMap::new());
+}
+
+#[tauri::command]
+async fn issue_session_token(master_password: String) -> Result<String, String> {
+    derive_master_key(&master_password)?;
+
+    let token = generate_token();
+    let now = std::time::Instant::now();
+    let mut grants = SESSION_GRANTS.lock().unwrap();
+    grants.retain(|_, grant| grant.expires_at > now);
+    grants.insert(
+        token.clone(),
+        SessionGrant {
+            master_password: zeroize::Zeroizing::new(master_password),
+            expires_at: now + SESSION_TOKEN_TTL,
+        },
+    );
+
+    Ok(token)
+}
+
+// Argon2 allocates all of m_cost up front; more than 1 GiB would stall or
+// abort the app rather than harden the vault
+const MAX_KDF_MEMORY_KIB: u32 = 1024 * 1024;
+
+#[derive(serde::Deserialize)]
+struct KdfParams {
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+// Re-derive master.hash and the vault key under new Argon2 parameters and a
+// fresh salt, decrypting with the cached session key (no password prompt)
+#[tauri::command]
+async fn rekey_with_session(session_token: String, new_params: KdfParams) -> Result<(), String> {
+    let grant = SESSION_GRANTS
+        .lock()
+        .unwrap()
+        .remove(&session_token)
+        .ok_or("Session token is invalid or already used")?;
+    if grant.expires_at <= std::time::Instant::now() {
+        return Err("Session token has expired".to_string());
+    }
+
+    if new_params.memory_kib > MAX_KDF_MEMORY_KIB {
+        return Err(format!(
+            "Invalid KDF parameters: memory must be at most {} KiB",
+            MAX_KDF_MEMORY_KIB
+        ));
+    }
+    let params = argon2::Params::new(
+        new_params.memory_kib,
+        new_params.iterations,
+        new_params.parallelism,
+        Some(32),
+    )
+    .map_err(|e| format!("Invalid KDF parameters: {}", e))?;
+
+    let store = load_password_store(None)?;
+    let store_json = zeroize::Zeroizing::new(
+        serde_json::to_string(&store).map_err(|e| format!("Failed to serialize store: {}", e))?,
+    );
+
+    rekey_vault(&grant.master_password, &store_json, params)?;
+    tracing::info!("Vault rekeyed with new KDF parameters");
+
+    Ok(())
+}
+
+// Writes master.hash and the vault under `params` and a fresh salt, then
+// moves an open session over to the new vault key. Returns that key.
+fn rekey_vault(
+    master_password: &str,
+    store_json: &str,
+    params: argon2::Params,
+) -> Result<Vec<u8>, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let argon2 = Argon2::new(
+        argon2::Algorithm::Argon2id,
+        argon2::Version::V0x13,
+        params.clone(),
+    );
+    let password_hash = argon2
+        .hash_password(master_password.as_bytes(), &salt)
+        .map_err(|e| format!("Failed to hash password: {}", e))?
+        .to_string();
+
+    let new_key = zeroize::Zeroizing::new(apply_keyfile(generate_key_with_params(
+        master_password,
+        salt.as_str().as_bytes(),
+        params.clone(),
+    )?)?);
+    let (encrypted_data, nonce, compressed) = seal_store_json(store_json, &new_key)?;
+
+    // Same conflict check as every other save: never rekey over changes
+    // another device or process made since the store was read
+    let (mut encrypted_store, existing_content) = load_encrypted_store_with_content()?;
+    check_vault_unchanged(&encrypted_store, &existing_content)?;
+    encrypted_store.encrypted_data = encrypted_data;
+    encrypted_store.nonce = nonce;
+    encrypted_store.compressed = compressed;
+    encrypted_store.salt = general_purpose::STANDARD.encode(salt.as_str().as_bytes());
+    encrypted_store.iterations = params.t_cost();
+    encrypted_store.kdf_params = Some(kdf_params_header(&params));
+    let content = serde_json::to_string_pretty(&encrypted_store)
+        .map_err(|e| format!("Failed to serialize encrypted store: {}", e))?;
+
+    // Never write a vault the new key can't open
+    decrypt_bytes(&encrypted_store.encrypted_data, &encrypted_store.nonce, &new_key)
+        .map_err(|e| format!("Re-encrypted vault failed verification: {}", e))?;
+
+    // master.hash and the vault must change together. The old hash is
+    // journaled first, so a crash between the two writes is repaired on the
+    // next unlock (see finish_interrupted_rekey); a failed vault write rolls
+    // the hash back right away.
+    let hash_path = get_master_hash_path()?;
+    let journal_path = get_rekey_journal_path()?;
+    let previous_hash =
+        fs::read(&hash_path).map_err(|e| format!("Failed to read master password hash: {}", e))?;
+    write_atomically(&journal_path, &previous_hash)?;
+    write_atomically(&hash_path, password_hash.as_bytes())?;
+    if let Err(e) = write_atomically(&get_data_file_path()?, content.as_bytes()) {
+        let _ = write_atomically(&hash_path, &previous_hash);
+        let _ = fs::remove_file(&journal_path);
+        return Err(e);
+    }
+    let _ = fs::remove_file(&journal_path);
+    remember_vault_fingerprint(&content);
+
+    refresh_session_key(&new_key);
+    Ok(new_key.to_vec())
+}
+
+// master.hash as it was before an in-progress rekey
+fn get_rekey_journal_path() -> Result<PathBuf, String> {
+    Ok(get_vault_dir()?.join("master.hash.prev"))
+}
+
+// Vault key for `password` under a PHC hash string, or None if it doesn't match
+fn key_for_hash(password: &str, stored_hash: &str) -> Result<Option<Vec<u8>>, String> {
+    let parsed_hash = PasswordHash::new(stored_hash.trim())
+        .map_err(|e| format!("Failed to parse password hash: {}", e))?;
+    if Argon2::default()
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .is_err()
+    {
+        return Ok(None);
+    }
+
+    let salt = parsed_hash
+        .salt
+        .ok_or_else(|| "Master password hash has no salt".to_string())?;
+    let params = argon2::Params::try_from(&parsed_hash)
+        .map_err(|e| format!("Failed to read KDF parameters: {}", e))?;
+    let key = apply_keyfile(generate_key_with_params(password, salt.as_str().as_bytes(), params)?)?;
+    Ok(Some(key))
+}
+
+fn vault_opens_with(key: &[u8]) -> bool {
+    load_encrypted_store()
+        .is_ok_and(|store| decrypt_bytes(&store.encrypted_data, &store.nonce, key).is_ok())
+}
+
+// A rekey that died between writing master.hash and the vault leaves a hash
+// that doesn't match the vault. Whichever of the new and journaled hashes
+// actually opens the vault wins; the journal is kept if neither can be checked
+// (e.g. wrong password) so a later unlock can try again.
+fn finish_interrupted_rekey(password: &str) -> Result<(), String> {
+    let journal_path = get_rekey_journal_path()?;
+    if !journal_path.exists() {
+        return Ok(());
+    }
+
+    let hash_path = get_master_hash_path()?;
+    let current_hash = fs::read_to_string(&hash_path)
+        .map_err(|e| format!("Failed to read master password hash: {}", e))?;
+    if let Some(key) = key_for_hash(password, &current_hash)? {
+        if vault_opens_with(&zeroize::Zeroizing::new(key)) {
+            let _ = fs::remove_file(&journal_path);
+            return Ok(());
+        }
+    }
+
+    let previous_hash = fs::read_to_string(&journal_path)
+        .map_err(|e| format!("Failed to read rekey journal: {}", e))?;
+    if let Some(key) = key_for_hash(password, &previous_hash)? {
+        if vault_opens_with(&zeroize::Zeroizing::new(key)) {
+            write_atomically(&hash_path, previous_hash.as_bytes())?;
+            let _ = fs::remove_file(&journal_path);
+            tracing::warn!("Rolled back master.hash left by an interrupted rekey");
+        }
+    }
+    Ok(())
+}
+
+// Per-component max of the stored and default Argon2 cost, so a vault rekeyed
+// with more memory but fewer passes keeps its memory. None when nothing is
+// below this build's default.
+fn upgraded_params(params: &argon2::Params) -> Option<argon2::Params> {
+    let current = argon2::Params::default();
+    if params.m_cost() >= current.m_cost()
+        && params.t_cost() >= current.t_cost()
+        && params.p_cost() >= current.p_cost()
+    {
+        return None;
+    }
+
+    argon2::Params::new(
+        params.m_cost().max(current.m_cost()),
+        params.t_cost().max(current.t_cost()),
+        params.p_cost().max(current.p_cost()),
+        params.output_len(),
+    )
+    .ok()
+}
+
+const KDF_UPGRADE_BACKOFF: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+const KDF_UPGRADE_MAX_BACKOFF: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+struct KdfUpgradeFailures {
+    count: u32,
+    retry_at: std::time::Instant,
+}
+
+lazy_static::lazy_static! {
+    // A rekey rewrites the whole vault, so a failing one isn't retried on every verify
+    static ref KDF_UPGRADE_FAILURES: Mutex<Option<KdfUpgradeFailures>> = Mutex::new(None);
+}
+
+fn kdf_upgrade_due() -> bool {
+    KDF_UPGRADE_FAILURES
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map_or(true, |failures| std::time::Instant::now() >= failures.retry_at)
+}
+
+fn record_kdf_upgrade_failure() {
+    let mut failures = KDF_UPGRADE_FAILURES.lock().unwrap();
+    let count = failures.as_ref().map_or(0, |f| f.count) + 1;
+    let backoff = KDF_UPGRADE_BACKOFF
+        .saturating_mul(1 << (count - 1).min(7))
+        .min(KDF_UPGRADE_MAX_BACKOFF);
+    *failures = Some(KdfUpgradeFailures {
+        count,
+        retry_at: std::time::Instant::now() + backoff,
+    });
+}
+
+// Brings an old-cost vault up to `params`. The vault key follows the hash
+// parameters, so the vault is re-encrypted along with master.hash.
+fn upgrade_kdf(master_password: &str, old_key: &[u8], params: argon2::Params) -> Result<Vec<u8>, String> {
+    let encrypted_store = load_encrypted_store()?;
+    let plaintext = decrypt_bytes(&encrypted_store.encrypted_data, &encrypted_store.nonce, old_key)?;
+    let store_json = zeroize::Zeroizing::new(unseal_store_json(plaintext, encrypted_store.compressed)?);
+
+    let new_key = rekey_vault(master_password, &store_json, params)?;
+    tracing::info!("Master password rehashed with current KDF parameters");
+    Ok(new_key)
+}
+
+lazy_static::lazy_static! {
+    // Digest of the last value we put on the clipboard, never the value itself
+    static ref LAST_CLIPBOARD_DIGEST: Mutex<Option<[u8; 32]>> = Mutex::new(None);
+}
+
+fn clipboard_digest(text: &str) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(text.as_bytes()).into()
+}
+
+fn write_clipboard(app_handle: &tauri::AppHandle, text: String) -> Result<(), String> {
+    let digest = clipboard_digest(&text);
+    app_handle
+        .clipboard()
+        .write_text(text)
+        .map_err(|e| format!("Failed to write clipboard: {}", e))?;
+    *LAST_CLIPBOARD_DIGEST.lock().unwrap() = Some(digest);
+    Ok(())
+}
+
+fn clear_own_clipboard(app_handle: &tauri::AppHandle) {
+    if load_settings().map(|s| s.clear_clipboard_on_hide).unwrap_or(true) {
+        wipe_own_clipboard(app_handle);
+    }
+}
+
+// Only clears when the clipboard still holds what we copied, so text the user
+// copied from another app afterwards is left alone
+fn wipe_own_clipboard(app_handle: &tauri::AppHandle) {
+    let mut last = LAST_CLIPBOARD_DIGEST.lock().unwrap();
+    let Some(digest) = *last else {
+        return;
+    };
+
+    match app_handle.clipboard().read_text() {
+        Ok(current) if clipboard_digest(&current) == digest => {
+            if let Err(e) = app_handle.clipboard().clear() {
+                tracing::warn!("Failed to clear clipboard: {}", e);
+                return;
+            }
+            tracing::debug!("Cleared copied value from clipboard");
+        }
+        _ => {}
+    }
+    *last = None;
+}
+
+// (process name, display name), matched case-insensitively against running processes
+#[cfg(target_os = "linux")]
+const CLIPBOARD_MANAGERS: &[(&str, &str)] = &[
+    ("copyq", "CopyQ"),
+    ("gpaste-daemon", "GPaste"),
+    ("clipman", "clipman"),
+    ("xfce4-clipman", "Xfce Clipman"),
+    ("klipper", "Klipper"),
+    ("clipit", "ClipIt"),
+    ("parcellite", "Parcellite"),
+    ("diodon", "Diodon"),
+    ("greenclip", "greenclip"),
+    ("clipmenud", "clipmenu"),
+    ("cliphist", "cliphist"),
+];
+
+#[cfg(target_os = "macos")]
+const CLIPBOARD_MANAGERS: &[(&str, &str)] = &[
+    ("maccy", "Maccy"),
+    ("paste", "Paste"),
+    ("pastebot", "Pastebot"),
+    ("copyclip", "CopyClip"),
+    ("flycut", "Flycut"),
+    ("clipy", "Clipy"),
+    ("alfred", "Alfred"),
+    ("raycast", "Raycast"),
+];
+
+#[cfg(target_os = "windows")]
+const CLIPBOARD_MANAGERS: &[(&str, &str)] = &[
+    ("ditto.exe", "Ditto"),
+    ("copyq.exe", "CopyQ"),
+    ("clipboardfusion.exe", "ClipboardFusion"),
+    ("clipclip.exe", "ClipClip"),
+    ("clipdiary.exe", "Clipdiary"),
+];
+
+#[derive(serde::Serialize)]
+struct ClipboardSafety {
+    safe: bool,
+    platform: &'static str,
+    managers: Vec<String>,
+    warning: Option<String>,
+}
+
+#[cfg(target_os = "linux")]
+fn running_process_names() -> Result<Vec<String>, String> {
+    let proc_dir = std::fs::read_dir("/proc").map_err(|e| format!("Failed to list processes: {}", e))?;
+
+    Ok(proc_dir
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().chars().all(|c| c.is_ascii_digit()))
+        .filter_map(|entry| fs::read_to_string(entry.path().join("comm")).ok())
+        .map(|name| name.trim().to_lowercase())
+        .collect())
+}
+
+#[cfg(target_os = "macos")]
+fn running_process_names() -> Result<Vec<String>, String> {
+    let output = std::process::Command::new("ps")
+        .args(["-axco", "comm="])
+        .output()
+        .map_err(|e| format!("Failed to list processes: {}", e))?;
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|name| name.trim().to_lowercase())
+        .collect())
+}
+
+#[cfg(target_os = "windows")]
+fn running_process_names() -> Result<Vec<String>, String> {
+    let output = std::process::Command::new("tasklist")
+        .args(["/fo", "csv", "/nh"])
+        .output()
+        .map_err(|e| format!("Failed to list processes: {}", e))?;
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split(',').next())
+        .map(|name| name.trim_matches('"').to_lowercase())
+        .collect())
+}
+
+// Clipboard managers keep their own history, so a copied secret can outlive
+// the auto-clear. Called by the UI before copying anything sensitive.
+#[tauri::command]
+async fn clipboard_is_safe() -> Result<ClipboardSafety, String> {
+    let running = running_process_names()?;
+    let mut managers: Vec<String> = CLIPBOARD_MANAGERS
+        .iter()
+        .filter(|(process, _)| running.iter().any(|name| name == process))
+        .map(|(_, display)| display.to_string())
+        .collect();
+    managers.dedup();
+
+    let warning = (!managers.is_empty()).then(|| {
+        format!(
+            "{} may keep copied passwords in its history after Cocoon clears the clipboard",
+            managers.join(", ")
+        )
+    });
+
+    Ok(ClipboardSafety {
+        safe: managers.is_empty(),
+        platform: std::env::consts::OS,
+        managers,
+        warning,
+    })
+}
+
+fn hide_main_window(app_handle: &tauri::AppHandle, window: &tauri::WebviewWindow) {
+    let _ = window.hide();
+    clear_own_clipboard(app_handle);
+}
+
+#[tauri::command]
+async fn hide_window(app_handle: tauri::AppHandle) -> Result<(), String> {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        hide_main_window(&app_handle, &window);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn copy_to_clipboard(text: String, app_handle: tauri::AppHandle) -> Result<(), String> {
+    write_clipboard(&app_handle, text)
+}
+
+const DEFAULT_BLUR_HIDE_DELAY_MS: u64 = 250;
+const MAX_BLUR_HIDE_DELAY_MS: u64 = 5_000;
+
+// Set by the UI while a native sub-dialog (file picker, etc.) owns focus
+static SUPPRESS_AUTO_HIDE: AtomicBool = AtomicBool::new(false);
+
+#[tauri::command]
+async fn set_suppress_auto_hide(suppress: bool) -> Result<(), String> {
+    SUPPRESS_AUTO_HIDE.store(suppress, Ordering::SeqCst);
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_blur_hide_delay_ms(delay_ms: u64) -> Result<(), String> {
+    if delay_ms > MAX_BLUR_HIDE_DELAY_MS {
+        return Err(format!(
+            "Blur hide delay must be at most {} ms",
+            MAX_BLUR_HIDE_DELAY_MS
+        ));
+    }
+
+    let mut settings = load_settings()?;
+    settings.blur_hide_delay_ms = delay_ms;
+    save_settings(&settings)
+}
+
+const MAX_IDLE_TIMEOUT_MINS: u32 = 24 * 60;
+const IDLE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+fn validate_idle_timeout(idle_timeout_mins: Option<u32>) -> Result<(), String> {
+    match idle_timeout_mins {
+        Some(mins) if mins == 0 || mins > MAX_IDLE_TIMEOUT_MINS => Err(format!(
+            "Idle timeout must be between 1 and {} minutes",
+            MAX_IDLE_TIMEOUT_MINS
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[tauri::command]
+async fn set_idle_timeout_mins(idle_timeout_mins: Option<u32>) -> Result<(), String> {
+    validate_idle_timeout(idle_timeout_mins)?;
+    let mut settings = load_settings()?;
+    settings.idle_timeout_mins = idle_timeout_mins;
+    save_settings(&settings)
+}
+
+#[tauri::command]
+async fn set_hide_on_blur(hide_on_blur: bool) -> Result<(), String> {
+    let mut settings = load_settings()?;
+    settings.hide_on_blur = hide_on_blur;
+    save_settings(&settings)
+}
+
+// Overrides kept next to a vault's own files, so a vault in a synced folder
+// brings its policy along. Unset fields fall back to the global settings.
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct VaultPolicy {
+    idle_timeout_mins: Option<u32>,
+    hide_on_blur: Option<bool>,
+}
+
+// There's one vault per directory, so a vault is named by its directory;
+// None is the active one
+fn policy_vault_dir(vault_directory: Option<&str>) -> Result<PathBuf, String> {
+    match vault_directory {
+        Some(dir) => {
+            let dir = PathBuf::from(dir);
+            if !dir.is_dir() {
+                return Err(format!("Vault directory {} is not available", dir.display()));
+            }
+            // Only ever read or write a policy beside an actual Cocoon vault
+            if !dir.join("vault.cocoon").is_file() || !dir.join("master.hash").is_file() {
+                return Err(format!("{} does not contain a Cocoon vault", dir.display()));
+            }
+            Ok(dir)
+        }
+        None => get_vault_dir(),
+    }
+}
+
+fn load_vault_policy(vault_dir: &std::path::Path) -> Result<VaultPolicy, String> {
+    let path = vault_dir.join("vault-policy.json");
+    if !path.exists() {
+        return Ok(VaultPolicy::default());
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read vault policy: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse vault policy: {}", e))
+}
+
+// (idle timeout, hide on blur) for the active vault
+fn effective_lock_policy() -> (Option<u32>, bool) {
+    let settings = load_settings().unwrap_or_default();
+    let policy = get_vault_dir()
+        .and_then(|dir| load_vault_policy(&dir))
+        .unwrap_or_else(|e| {
+            tracing::warn!("Failed to load vault policy: {}", e);
+            VaultPolicy::default()
+        });
+
+    (
+        policy.idle_timeout_mins.or(settings.idle_timeout_mins),
+        policy.hide_on_blur.unwrap_or(settings.hide_on_blur),
+    )
+}
+
+#[tauri::command]
+async fn get_vault_policy(vault_directory: Option<String>) -> Result<VaultPolicy, String> {
+    load_vault_policy(&policy_vault_dir(vault_directory.as_deref())?)
+}
+
+#[tauri::command]
+async fn set_vault_policy(vault_directory: Option<String>, policy: VaultPolicy) -> Result<(), String> {
+    validate_idle_timeout(policy.idle_timeout_mins)?;
+    let json = serde_json::to_vec_pretty(&policy)
+        .map_err(|e| format!("Failed to serialize vault policy: {}", e))?;
+    write_atomically(
+        &policy_vault_dir(vault_directory.as_deref())?.join("vault-policy.json"),
+        &json,
+    )
+}
+
+lazy_static::lazy_static! {
+    static ref LAST_ACTIVITY: Mutex<std::time::Instant> = Mutex::new(std::time::Instant::now());
+}
+
+fn record_activity() {
+    *LAST_ACTIVITY.lock().unwrap() = std::time::Instant::now();
+}
+
+// Re-reads the policy on every tick so switching vaults or editing the
+// policy applies without a restart
+fn spawn_idle_watchdog(app_handle: tauri::AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(IDLE_CHECK_INTERVAL);
+        if SESSION_KEY.lock().unwrap().is_none() {
+            continue;
+        }
+
+        let (idle_timeout_mins, _) = effective_lock_policy();
+        let Some(mins) = idle_timeout_mins else {
+            continue;
+        };
+        let idle = LAST_ACTIVITY.lock().unwrap().elapsed();
+        if idle >= std::time::Duration::from_secs(u64::from(mins) * 60) {
+            tracing::info!(mins, "Locking vault after idle timeout");
+            lock_session(&app_handle);
+        }
+    });
+}
+
+#[tauri::command]
+async fn set_clear_clipboard_on_hide(enabled: bool) -> Result<(), String> {
+    let mut settings = load_settings()?;
+    settings.clear_clipboard_on_hide = enabled;
+    save_settings(&settings)
+}
+
+const DEFAULT_SYMBOLS: &str = "!@#$%^&*()_+-=[]{}|;:,.<>?";
+
+#[derive(serde::Deserialize, Clone, Default)]
+struct PasswordGenConfig {
+    length: usize,
+    include_uppercase: bool,
+    include_lowercase: bool,
+    include_numbers: bool,
+    include_symbols: bool,
+    // Replaces DEFAULT_SYMBOLS for sites that only accept a few symbols
+    #[serde(default)]
+    custom_symbols: Option<String>,
+    // Per-class minimums for policies like "at least 2 digits"
+    #[serde(default)]
+    min_lowercase: usize,
+    #[serde(default)]
+    min_uppercase: usize,
+    #[serde(default)]
+    min_digits: usize,
+    #[serde(default)]
+    min_symbols: usize,
+}
+
+// Custom symbols must be a distinct class: no letters, digits or whitespace
+fn symbol_charset(config: &PasswordGenConfig) -> Result<String, String> {
+    let custom = match &config.custom_symbols {
+        Some(custom) => custom,
+        None => return Ok(DEFAULT_SYMBOLS.to_string()),
+    };
+
+    if let Some(c) = custom
+        .chars()
+        .find(|c| c.is_alphanumeric() || c.is_whitespace() || c.is_control())
+    {
+        return Err(format!("'{}' is not allowed in custom symbols", c));
+    }
+
+    let mut symbols = String::new();
+    for c in custom.chars() {
+        if !symbols.contains(c) {
+            symbols.push(c);
+        }
+    }
+    if symbols.is_empty() {
+        return Err("Custom symbols must not be empty".to_string());
+    }
+    Ok(symbols)
+}
+
+fn generate_from_config(config: &PasswordGenConfig) -> Result<String, String> {
+    if config.length < 4 || config.length > 128 {
+        return Err("Password length must be between 4 and 128 characters".to_string());
+    }
+
+    let symbols = if config.include_symbols {
+        symbol_charset(config)?
+    } else {
+        String::new()
+    };
+    let classes = [
+        ("lowercase", config.include_lowercase, "abcdefghijklmnopqrstuvwxyz", config.min_lowercase),
+        ("uppercase", config.include_uppercase, "ABCDEFGHIJKLMNOPQRSTUVWXYZ", config.min_uppercase),
+        ("digit", config.include_numbers, "0123456789", config.min_digits),
+        ("symbol", config.include_symbols, symbols.as_str(), config.min_symbols),
+    ];
+
+    let mut charset = String::new();
+    let mut required = 0;
+    for (name, enabled, class_chars, min) in classes {
+        if enabled {
+            charset.push_str(class_chars);
+        } else if min > 0 {
+            return Err(format!("Minimum {} count requires {} characters to be enabled", name, name));
+        }
+        required += min;
+    }
+
+    if charset.is_empty() {
+        return Err("At least one character type must be selected".to_string());
+    }
+    if required > config.length {
+        return Err(format!(
+            "Character minimums add up to {}, more than the length of {}",
+            required, config.length
+        ));
+    }
+
+    let mut rng = OsRng;
+    let mut pick = |pool: &[char]| pool[(rng.next_u32() as usize) % pool.len()];
+
+    let mut password: Vec<char> = Vec::with_capacity(config.length);
+    for (_, _, class_chars, min) in classes {
+        let pool: Vec<char> = class_chars.chars().collect();
+        for _ in 0..min {
+            password.push(pick(&pool));
+        }
+    }
+
+    let chars: Vec<char> = charset.chars().collect();
+    while password.len() < config.length {
+        password.push(pick(&chars));
+    }
+
+    // Fisher-Yates, so the required characters aren't clustered at the front
+    for i in (1..password.len()).rev() {
+        let j = (rng.next_u32() as usize) % (i + 1);
+        password.swap(i, j);
+    }
+
+    Ok(password.into_iter().collect())
+}
+
+// Offline attack against a fast hash on commodity GPUs
+const CRACK_GUESSES_PER_SECOND: f64 = 1e10;
+
+fn charset_entropy_bits(password: &str) -> f64 {
+    let mut pool = 0u32;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if password.chars().any(|c| !c.is_ascii_alphanumeric()) {
+        pool += 33;
+    }
+
+    password.chars().count() as f64 * f64::from(pool.max(1)).log2()
+}
+
+// Length times the Shannon entropy of the character frequencies; catches
+// repetitive passwords ("aaaaaaaaA1!") that the charset estimate overrates
+fn shannon_entropy_bits(password: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in password.chars() {
+        *counts.entry(c).or_insert(0) += 1;
+    }
+
+    let length = password.chars().count() as f64;
+    let per_char: f64 = counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / length;
+            -p * p.log2()
+        })
+        .sum();
+    per_char * length
+}
+
+fn estimate_crack_time(password: &str) -> String {
+    let entropy_bits = charset_entropy_bits(password);
+    // On average the attacker finds it halfway through the keyspace
+    let seconds = 2f64.powf(entropy_bits - 1.0) / CRACK_GUESSES_PER_SECOND;
+
+    const MINUTE: f64 = 60.0;
+    const HOUR: f64 = 60.0 * MINUTE;
+    const DAY: f64 = 24.0 * HOUR;
+    const YEAR: f64 = 365.0 * DAY;
+
+    if seconds < 1.0 {
+        "instantly".to_string()
+    } else if seconds < MINUTE {
+        format!("{:.0} seconds", seconds)
+    } else if seconds < HOUR {
+        format!("{:.0} minutes", seconds / MINUTE)
+    } else if seconds < DAY {
+        format!("{:.0} hours", seconds / HOUR)
+    } else if seconds < YEAR {
+        format!("{:.0} days", seconds / DAY)
+    } else if seconds < 1000.0 * YEAR {
+        format!("{:.0} years", seconds / YEAR)
+    } else {
+        "centuries".to_string()
+    }
+}
+
+#[derive(serde::Serialize)]
+struct GeneratedPassword {
+    password: String,
+    strength: u8,
+    estimated_crack_time: String,
+}
+
+#[tauri::command]
+async fn generate_password(
+    length: usize,
+    include_uppercase: bool,
+    include_lowercase: bool,
+    include_numbers: bool,
+    include_symbols: bool,
+    custom_symbols: Option<String>,
+    min_lowercase: Option<usize>,
+    min_uppercase: Option<usize>,
+    min_digits: Option<usize>,
+    min_symbols: Option<usize>,
+) -> Result<GeneratedPassword, String> {
+    let password = generate_from_config(&PasswordGenConfig {
+        length,
+        include_uppercase,
+        include_lowercase,
+        include_numbers,
+        include_symbols,
+        custom_symbols,
+        min_lowercase: min_lowercase.unwrap_or(0),
+        min_uppercase: min_uppercase.unwrap_or(0),
+        min_digits: min_digits.unwrap_or(0),
+        min_symbols: min_symbols.unwrap_or(0),
+    })?;
+
+    Ok(GeneratedPassword {
+        strength: calculate_password_strength(&password),
+        estimated_crack_time: estimate_crack_time(&password),
+        password,
+    })
+}
+
+// Bare-string variant for callers that predate the strength report
+#[tauri::command]
+async fn generate_password_simple(
+    length: usize,
+    include_uppercase: bool,
+    include_lowercase: bool,
+    include_numbers: bool,
+    include_symbols: bool,
+) -> Result<String, String> {
+    generate_from_config(&PasswordGenConfig {
+        length,
+        include_uppercase,
+        include_lowercase,
+        include_numbers,
+        include_symbols,
+        ..Default::default()
+    })
+}
+
+const MAX_BULK_PASSWORDS: usize = 1000;
+// Collisions only happen with tiny configs (e.g. 4 digits); give up rather
+// than spin when the keyspace can't hold `count` distinct passwords
+const BULK_RETRIES_PER_PASSWORD: usize = 10;
+
+#[tauri::command]
+async fn generate_passwords_bulk(count: usize, config: PasswordGenConfig) -> Result<Vec<String>, String> {
+    if count == 0 || count > MAX_BULK_PASSWORDS {
+        return Err(format!(
+            "Count must be between 1 and {}",
+            MAX_BULK_PASSWORDS
+        ));
+    }
+
+    let mut seen = std::collections::HashSet::with_capacity(count);
+    let mut passwords = Vec::with_capacity(count);
+    let mut attempts = 0;
+    while passwords.len() < count {
+        if attempts == count * BULK_RETRIES_PER_PASSWORD {
+            return Err("Too many duplicate passwords; use a longer length or more character classes".to_string());
+        }
+        attempts += 1;
+
+        let password = generate_from_config(&config)?;
+        if seen.insert(password.clone()) {
+            passwords.push(password);
+        }
+    }
+
+    Ok(passwords)
+}
+
+// One word per line, lowercase; source for the {word} username token
+const USERNAME_WORDS: &str = include_str!("../resources/username-words.txt");
+
+lazy_static::lazy_static! {
+    static ref USERNAME_WORD_LIST: Vec<&'static str> = USERNAME_WORDS
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+}
+
+const MAX_RANDOM_TOKEN_LEN: usize = 64;
+
+// "https://accounts.google.com" -> "google"; anything odd is reduced to [a-z0-9-]
+fn site_label(site: &str) -> Option<String> {
+    let domain = registrable_domain(site).unwrap_or_else(|| site.trim().to_lowercase());
+    let label: String = domain
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    if label.is_empty() {
+        None
+    } else {
+        Some(label)
+    }
+}
+
+// Expands {word}, {random:N}, {site} and {number} in a template such as
+// "{site}+{random:6}@example.com"; everything else is copied as-is
+#[tauri::command]
+async fn generate_username(pattern: String, site: Option<String>) -> Result<String, String> {
+    let words = &*USERNAME_WORD_LIST;
+    let alphanumeric: Vec<char> = "abcdefghijklmnopqrstuvwxyz0123456789".chars().collect();
+    let mut rng = OsRng;
+
+    let mut username = String::new();
+    let mut rest = pattern.as_str();
+    while let Some(start) = rest.find('{') {
+        username.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| "Unclosed '{' in username pattern".to_string())?;
+        let token = &rest[start + 1..end];
+
+        match token.split_once(':') {
+            None if token == "word" => {
+                username.push_str(words[(rng.next_u32() as usize) % words.len()]);
+            }
+            None if token == "number" => {
+                username.push_str(&(1000 + rng.next_u32() % 9000).to_string());
+            }
+            None if token == "site" => {
+                let label = site
+                    .as_deref()
+                    .and_then(site_label)
+                    .ok_or_else(|| "Pattern uses {site} but no site was given".to_string())?;
+                username.push_str(&label);
+            }
+            Some(("random", len)) => {
+                let len: usize = len
+                    .parse()
+                    .ok()
+                    .filter(|len| (1..=MAX_RANDOM_TOKEN_LEN).contains(len))
+                    .ok_or_else(|| {
+                        format!("{{random:N}} needs N between 1 and {}", MAX_RANDOM_TOKEN_LEN)
+                    })?;
+                for _ in 0..len {
+                    username.push(alphanumeric[(rng.next_u32() as usize) % alphanumeric.len()]);
+                }
+            }
+            _ => return Err(format!("Unknown username token '{{{}}}'", token)),
+        }
+
+        rest = &rest[end + 1..];
+    }
+    username.push_str(rest);
+
+    if username.trim().is_empty() {
+        return Err("Username pattern produced an empty username".to_string());
+    }
+    Ok(username)
+}
+
+#[derive(serde::Serialize)]
+struct StrengthCheckedPassword {
+    password: String,
+    strength: u8,
+    threshold_met: bool,
+}
+
+const MAX_STRENGTH_ATTEMPTS: u32 = 1000;
+
+// Regenerate until the scorer clears `min_strength`; otherwise return the best try
+#[tauri::command]
+async fn generate_password_min_strength(
+    config: PasswordGenConfig,
+    min_strength: u8,
+    max_attempts: u32,
+) -> Result<StrengthCheckedPassword, String> {
+    // calculate_password_strength never scores above 100
+    if min_strength > 100 {
+        return Err(format!("Minimum strength must be at most 100, got {}", min_strength));
+    }
+    if max_attempts > MAX_STRENGTH_ATTEMPTS {
+        return Err(format!("Max attempts must be at most {}", MAX_STRENGTH_ATTEMPTS));
+    }
+    let mut best: Option<(String, u8)> = None;
+
+    for _ in 0..max_attempts.max(1) {
+        let password = generate_from_config(&config)?;
+        let strength = calculate_password_strength(&password);
+
+        if strength >= min_strength {
+            return Ok(StrengthCheckedPassword {
+                password,
+                strength,
+                threshold_met: true,
+            });
+        }
+
+        let is_best = match &best {
+            Some((_, best_strength)) => strength > *best_strength,
+            None => true,
+        };
+        if is_best {
+            best = Some((password, strength));
+        }
+    }
+
+    let (password, strength) = best.ok_or("No password generated")?;
+    Ok(StrengthCheckedPassword {
+        password,
+        strength,
+        threshold_met: false,
+    })
+}
+
+#[tauri::command]
+async fn export_vault(export_password: String, master_password: Option<String>) -> Result<String, String> {
+    let store = load_password_store(master_password.as_deref())?;
+    build_export(&store, &export_password)
+}
+
+// Paper backup: plain text, one section per tag. An entry is printed once,
+// under its first tag, so a password never appears on the sheet twice.
+#[tauri::command]
+async fn export_printable(master_password: Option<String>) -> Result<String, String> {
+    use std::fmt::Write;
+
+    let mut store = load_password_store(master_password.as_deref())?;
+    store
+        .entries
+        .sort_by_key(|entry| entry.title.to_lowercase());
+
+    let rule = "=".repeat(72);
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", rule);
+    let _ = writeln!(out, "  WARNING: UNENCRYPTED PASSWORD LIST");
+    let _ = writeln!(out, "  Anyone who can read this page can sign in to these accounts.");
+    let _ = writeln!(out, "  Store it somewhere physically secure and shred old copies.");
+    let _ = writeln!(out, "{}", rule);
+    let _ = writeln!(
+        out,
+        "Cocoon export, {} ({} entries)",
+        chrono::Utc::now().format("%Y-%m-%d %H:%M UTC"),
+        store.entries.len()
+    );
+
+    let mut sections: std::collections::BTreeMap<String, (&str, Vec<&PasswordEntry>)> =
+        std::collections::BTreeMap::new();
+    let mut untagged = Vec::new();
+    for entry in &store.entries {
+        match entry.tags.iter().map(|tag| tag.trim()).find(|tag| !tag.is_empty()) {
+            Some(tag) => sections
+                .entry(tag.to_lowercase())
+                .or_insert_with(|| (tag, Vec::new()))
+                .1
+                .push(entry),
+            None => untagged.push(entry),
+        }
+    }
+
+    let sections = sections
+        .into_values()
+        .chain((!untagged.is_empty()).then_some(("Untagged", untagged)));
+    for (name, entries) in sections {
+        let dashes = "-".repeat(68usize.saturating_sub(name.chars().count()));
+        let _ = writeln!(out, "\n--- {} {}", name, dashes);
+        for entry in entries {
+            write_printable_entry(&mut out, entry);
+        }
+    }
+
+    Ok(out)
+}
+
+fn write_printable_entry(out: &mut String, entry: &PasswordEntry) {
+    use std::fmt::Write;
+
+    let _ = writeln!(out, "\n{}", entry.title);
+    let _ = writeln!(out, "  Username: {}", entry.username);
+    let _ = writeln!(out, "  Password: {}", entry.password);
+    if let Some(url) = &entry.url {
+        let _ = writeln!(out, "  URL:      {}", url);
+    }
+    if let Some(secret) = &entry.totp_secret {
+        let _ = writeln!(out, "  TOTP:     {}", secret);
+    }
+    if let Some(notes) = entry.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+        let _ = writeln!(out, "  Notes:");
+        for line in notes.lines() {
+            let _ = writeln!(out, "    {}", line);
+        }
+    }
+}
+
+// Splits at exact character counts, not word boundaries: a password copied
+// off paper has to come back character for character
+fn wrap_chars(text: &str, max_chars: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.is_empty() {
+        return vec![String::new()];
+    }
+    chars
+        .chunks(max_chars.max(1))
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
+// A4 sheet for a safe-deposit box. Cocoon stores no master-password hint and
+// has no recovery key, so there's a blank line to write the hint by hand.
+// The built-in PDF fonts only cover Latin-1; other characters won't render.
+#[tauri::command]
+async fn export_emergency_pdf(
+    include_passwords: Option<bool>,
+    master_password: Option<String>,
+) -> Result<Vec<u8>, String> {
+    use printpdf::{BuiltinFont, Mm, PdfDocument};
+
+    const PAGE_WIDTH: f32 = 210.0;
+    const PAGE_HEIGHT: f32 = 297.0;
+    const MARGIN: f32 = 15.0;
+    const LINE_HEIGHT: f32 = 5.0;
+
+    let include_passwords = include_passwords.unwrap_or(false);
+    let mut store = load_password_store(master_password.as_deref())?;
+    store
+        .entries
+        .sort_by_key(|entry| entry.title.to_lowercase());
+
+    let (doc, first_page, first_layer) = PdfDocument::new(
+        "Cocoon emergency sheet",
+        Mm(PAGE_WIDTH),
+        Mm(PAGE_HEIGHT),
+        "Layer 1",
+    );
+    let font = doc
+        .add_builtin_font(BuiltinFont::Helvetica)
+        .map_err(|e| format!("Failed to build PDF: {}", e))?;
+    let bold = doc
+        .add_builtin_font(BuiltinFont::HelveticaBold)
+        .map_err(|e| format!("Failed to build PDF: {}", e))?;
+
+    let mut layer = doc.get_page(first_page).get_layer(first_layer);
+    let mut y = PAGE_HEIGHT - MARGIN;
+
+    let banner = [
+        "KEEP PHYSICALLY SECURE",
+        "This sheet lists your Cocoon accounts. Store it in a safe or safe-deposit box",
+        "and destroy old copies.",
+    ];
+    for (i, line) in banner.iter().enumerate() {
+        let size = if i == 0 { 18.0 } else { 10.0 };
+        layer.use_text(*line, size, Mm(MARGIN), Mm(y), &bold);
+        y -= if i == 0 { 8.0 } else { LINE_HEIGHT };
+    }
+    y -= LINE_HEIGHT;
+
+    layer.use_text(
+        format!(
+            "Generated {} - {} entries",
+            chrono::Utc::now().format("%Y-%m-%d %H:%M UTC"),
+            store.entries.len()
+        ),
+        10.0,
+        Mm(MARGIN),
+        Mm(y),
+        &font,
+    );
+    y -= LINE_HEIGHT * 2.0;
+    layer.use_text(
+        "Master password hint: ______________________________________________",
+        10.0,
+        Mm(MARGIN),
+        Mm(y),
+        &font,
+    );
+    y -= LINE_HEIGHT * 2.0;
+
+    // (header, x offset in mm, max characters)
+    let columns: &[(&str, f32, usize)] = if include_passwords {
+        &[("Title", 0.0, 24), ("Username", 45.0, 24), ("URL", 90.0, 24), ("Password", 135.0, 26)]
+    } else {
+        &[("Title", 0.0, 30), ("Username", 60.0, 30), ("URL", 120.0, 34)]
+    };
+
+    let mut new_page = true;
+    for entry in &store.entries {
+        let values = [
+            entry.title.as_str(),
+            entry.username.as_str(),
+            entry.url.as_deref().unwrap_or(""),
+            entry.password.as_str(),
+        ];
+        let cells: Vec<Vec<String>> = columns
+            .iter()
+            .zip(values)
+            .map(|((_, _, max_chars), value)| wrap_chars(value, *max_chars))
+            .collect();
+        let row_height = LINE_HEIGHT * cells.iter().map(Vec::len).max().unwrap_or(1) as f32;
+
+        if y - row_height < MARGIN {
+            let (page, page_layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+            layer = doc.get_page(page).get_layer(page_layer);
+            y = PAGE_HEIGHT - MARGIN;
+            new_page = true;
+        }
+        if new_page {
+            for (header, x, _) in columns {
+                layer.use_text(*header, 10.0, Mm(MARGIN + x), Mm(y), &bold);
+            }
+            y -= LINE_HEIGHT * 1.5;
+            new_page = false;
+        }
+
+        for ((_, x, _), lines) in columns.iter().zip(&cells) {
+            for (i, line) in lines.iter().enumerate() {
+                let line_y = y - LINE_HEIGHT * i as f32;
+                layer.use_text(line.as_str(), 9.0, Mm(MARGIN + x), Mm(line_y), &font);
+            }
+        }
+        y -= row_height;
+    }
+
+    doc.save_to_bytes()
+        .map_err(|e| format!("Failed to build PDF: {}", e))
+}
+
+fn build_export(store: &PasswordStore, export_password: &str) -> Result<String, String> {
+    let export_data = serde_json::to_string_pretty(store)
+        .map_err(|e| format!("Failed to serialize vault: {}", e))?;
+
+    // Encrypt export with provided password
+    let salt = SaltString::generate(&mut OsRng);
+    let key = generate_key_from_password(export_password, salt.as_str().as_bytes())?;
+    let (encrypted_data, nonce) = encrypt_data(&export_data, &key)?;
+
+    let export_structure = serde_json::json!({
+        "version": 1,
+        "encrypted_data": encrypted_data,
+        "nonce": nonce,
+        "salt": general_purpose::STANDARD.encode(salt.as_str().as_bytes()),
+        "exported_at": chrono::Utc::now().to_rfc3339()
+    });
+
+    serde_json::to_string_pretty(&export_structure)
+        .map_err(|e| format!("Failed to serialize export: {}", e))
+}
+
+fn decrypt_export(export_data: &str, export_password: &str) -> Result<PasswordStore, String> {
+    let export_structure: serde_json::Value = serde_json::from_str(export_data)
+        .map_err(|e| format!("Failed to parse export: {}", e))?;
+
+    let field = |name: &str| {
+        export_structure[name]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| format!("Export is missing '{}'", name))
+    };
+
+    let salt = general_purpose::STANDARD
+        .decode(field("salt")?)
+        .map_err(|e| format!("Failed to decode export salt: {}", e))?;
+    let key = generate_key_from_password(export_password, &salt)?;
+    let decrypted_data = decrypt_data(&field("encrypted_data")?, &field("nonce")?, &key)?;
+
+    serde_json::from_str(&decrypted_data)
+        .map_err(|e| format!("Failed to parse exported vault: {}", e))
+}
+
+// Share one credential: same envelope as `export_vault`, with a single entry
+#[tauri::command]
+async fn export_entry(
+    entry_id: u32,
+    export_password: String,
+    master_password: Option<String>,
+) -> Result<String, String> {
+    let entry =
+        find_entry(entry_id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+
+    let snippet = PasswordStore {
+        entries: vec![entry],
+        ..PasswordStore::default()
+    };
+    build_export(&snippet, &export_password)
+}
+
+const MAX_SHARE_TTL_SECS: u64 = 600;
+const SHARE_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+lazy_static::lazy_static! {
+    // Running share servers by token; dropping a sender stops its server
+    static ref SHARE_SERVERS: Mutex<HashMap<String, tokio::sync::oneshot::Sender<()>>> =
+        Mutex::new(HashMap::new());
+}
+
+#[derive(serde::Serialize)]
+struct ShareLink {
+    url: String,
+    token: String,
+    expires_at: String,
+}
+
+// Connecting a UDP socket sends nothing; it only picks the outbound interface
+fn lan_ip() -> std::net::IpAddr {
+    std::net::UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| {
+            socket.connect("192.0.2.1:80")?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip())
+        .unwrap_or(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn share_response(entry: &PasswordEntry) -> zeroize::Zeroizing<String> {
+    let body = zeroize::Zeroizing::new(format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\">\
+         <meta name=\"viewport\" content=\"width=device-width\"><title>Cocoon share</title></head>\
+         <body style=\"font-family:sans-serif;max-width:32em;margin:2em auto\">\
+         <h1>{}</h1><p>Username</p><pre>{}</pre><p>Password</p><pre>{}</pre>\
+         <p><small>This page can't be opened again.</small></p></body></html>",
+        escape_html(&entry.title),
+        escape_html(&entry.username),
+        escape_html(&entry.password),
+    ));
+
+    zeroize::Zeroizing::new(format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\
+         Cache-Control: no-store\r\nReferrer-Policy: no-referrer\r\n\
+         Content-Security-Policy: default-src 'none'; style-src 'unsafe-inline'\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body.as_str()
+    ))
+}
+
+// Returns true once the secret page has been served
+async fn handle_share_connection(
+    acceptor: &tokio_rustls::TlsAcceptor,
+    stream: tokio::net::TcpStream,
+    token: &str,
+    response: &str,
+) -> Result<bool, String> {
+    use subtle::ConstantTimeEq;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut tls = acceptor.accept(stream).await.map_err(|e| e.to_string())?;
+
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+        let read = tls.read(&mut buf).await.map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let path = request
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("GET "))
+        .and_then(|rest| rest.split(' ').next())
+        .unwrap_or_default();
+    let expected = format!("/s/{}", token);
+    // Favicon requests and the like get a 404 without using up the link
+    let matched: bool = path.as_bytes().ct_eq(expected.as_bytes()).into();
+
+    let not_found = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    tls.write_all(if matched { response } else { not_found }.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+    let _ = tls.shutdown().await;
+
+    Ok(matched)
+}
+
+async fn serve_share(
+    listener: tokio::net::TcpListener,
+    acceptor: tokio_rustls::TlsAcceptor,
+    token: String,
+    response: zeroize::Zeroizing<String>,
+    ttl: std::time::Duration,
+    mut stop: tokio::sync::oneshot::Receiver<()>,
+) {
+    let deadline = tokio::time::sleep(ttl);
+    tokio::pin!(deadline);
+
+    loop {
+        tokio::select! {
+            _ = &mut deadline => break,
+            _ = &mut stop => break,
+            accepted = listener.accept() => {
+                let Ok((stream, _)) = accepted else { continue };
+                let handled = tokio::time::timeout(
+                    SHARE_REQUEST_TIMEOUT,
+                    handle_share_connection(&acceptor, stream, &token, &response),
+                )
+                .await;
+                match handled {
+                    Ok(Ok(true)) => break,
+                    Ok(Ok(false)) => {}
+                    Ok(Err(e)) => tracing::debug!("Share connection failed: {}", e),
+                    Err(_) => tracing::debug!("Share connection timed out"),
+                }
+            }
+        }
+    }
+
+    SHARE_SERVERS.lock().unwrap().remove(&token);
+    // `response` is zeroized as it drops here
+    tracing::info!("Share server stopped");
+}
+
+// Serves the entry once over HTTPS (self-signed, so the browser will warn) on
+// the LAN address, then shuts down after the first view or `ttl_secs`
+#[tauri::command]
+async fn start_share_server(
+    entry_id: u32,
+    ttl_secs: u64,
+    master_password: Option<String>,
+) -> Result<ShareLink, String> {
+    let entry =
+        find_entry(entry_id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+    let ttl = std::time::Duration::from_secs(ttl_secs.clamp(1, MAX_SHARE_TTL_SECS));
+
+    let ip = lan_ip();
+    let certified = rcgen::generate_simple_self_signed(vec![ip.to_string()])
+        .map_err(|e| format!("Failed to create certificate: {}", e))?;
+    let key = rustls::pki_types::PrivateKeyDer::Pkcs8(
+        rustls::pki_types::PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der()),
+    );
+    let tls_config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(|e| format!("Failed to configure TLS: {}", e))?
+    .with_no_client_auth()
+    .with_single_cert(vec![certified.cert.der().clone()], key)
+    .map_err(|e| format!("Failed to configure TLS: {}", e))?;
+
+    let listener = tokio::net::TcpListener::bind((ip, 0))
+        .await
+        .map_err(|e| format!("Failed to start share server: {}", e))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to start share server: {}", e))?
+        .port();
+
+    let token = generate_token();
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel();
+    SHARE_SERVERS.lock().unwrap().insert(token.clone(), stop_tx);
+
+    tauri::async_runtime::spawn(serve_share(
+        listener,
+        tokio_rustls::TlsAcceptor::from(Arc::new(tls_config)),
+        token.clone(),
+        share_response(&entry),
+        ttl,
+        stop_rx,
+    ));
+    tracing::info!(entry_id, port, "Share server started");
+
+    let host = match ip {
+        std::net::IpAddr::V6(ip) => format!("[{}]", ip),
+        std::net::IpAddr::V4(ip) => ip.to_string(),
+    };
+    Ok(ShareLink {
+        url: format!("https://{}:{}/s/{}", host, port, token),
+        token,
+        expires_at: (chrono::Utc::now() + chrono::Duration::seconds(ttl.as_secs() as i64))
+            .to_rfc3339(),
+    })
+}
+
+#[tauri::command]
+async fn stop_share_server(token: String) -> Result<(), String> {
+    SHARE_SERVERS.lock().unwrap().remove(&token);
+    Ok(())
+}
+
+#[tauri::command]
+async fn import_entry(
+    blob: String,
+    import_password: String,
+    master_password: Option<String>,
+) -> Result<u32, String> {
+    let snippet = decrypt_export(&blob, &import_password)?;
+    let mut entry = snippet
+        .entries
+        .into_iter()
+        .next()
+        .ok_or("Shared snippet contains no entry")?;
+
+    let mut store = load_password_store(master_password.as_deref())?;
+    let entry_id = store.next_id;
+    entry.id = entry_id;
+    entry.modified_at = chrono::Utc::now().to_rfc3339();
+    store.entries.push(entry);
+    store.next_id += 1;
+
+    save_password_store(&store, master_password.as_deref())?;
+
+    Ok(entry_id)
+}
+
+#[derive(serde::Deserialize, Clone, Copy)]
+enum MergeStrategy {
+    Skip,
+    Overwrite,
+    KeepBoth,
+}
+
+#[derive(serde::Serialize, Default)]
+struct ImportResult {
+    added: usize,
+    skipped: usize,
+    overwritten: usize,
+    kept_both: usize,
+}
+
+// Matching key for imports: scheme/www/trailing slash and case don't make a new account
+fn normalized_identity(entry: &PasswordEntry) -> (String, String) {
+    let url = entry
+        .url
+        .as_deref()
+        .unwrap_or("")
+        .trim()
+        .to_lowercase();
+    let url = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(&url);
+    let url = url.strip_prefix("www.").unwrap_or(url).trim_end_matches('/');
+
+    // Without a URL the username alone is far too common ("admin", an email)
+    // to mean the same account, so the title has to match as well
+    let site = if url.is_empty() {
+        format!("title:{}", entry.title.trim().to_lowercase())
+    } else {
+        url.to_string()
+    };
+
+    (site, entry.username.trim().to_lowercase())
+}
+
+fn new_login_entry(
+    title: String,
+    username: String,
+    password: String,
+    url: Option<String>,
+    notes: Option<String>,
+) -> PasswordEntry {
+    let password_strength = calculate_password_strength(&password);
+
+    PasswordEntry {
+        id: 0, // assigned when merged into a store
+        title,
+        username,
+        password,
+        url: normalize_optional_url(url),
+        notes,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        modified_at: chrono::Utc::now().to_rfc3339(),
+        password_strength,
+        password_changed_at: Some(chrono::Utc::now().to_rfc3339()),
+        ..Default::default()
+    }
+}
+
+fn merge_imported_entries(
+    store: &mut PasswordStore,
+    incoming: Vec<PasswordEntry>,
+    strategy: MergeStrategy,
+) -> ImportResult {
+    let mut result = ImportResult::default();
+
+    for mut entry in incoming {
+        let identity = normalized_identity(&entry);
+        let existing = store
+            .entries
+            .iter()
+            .position(|e| normalized_identity(e) == identity);
+
+        match (existing, strategy) {
+            (Some(_), MergeStrategy::Skip) => {
+                result.skipped += 1;
+                continue;
+            }
+            (Some(pos), MergeStrategy::Overwrite) => {
+                let existing = &mut store.entries[pos];
+                existing.password_strength = calculate_password_strength(&entry.password);
+                if existing.password != entry.password {
+                    existing.password_changed_at = Some(chrono::Utc::now().to_rfc3339());
+                }
+                existing.password = entry.password;
+                existing.notes = entry.notes;
+                existing.modified_at = chrono::Utc::now().to_rfc3339();
+                result.overwritten += 1;
+                continue;
+            }
+            (Some(_), MergeStrategy::KeepBoth) => result.kept_both += 1,
+            (None, _) => result.added += 1,
+        }
+
+        entry.id = store.next_id;
+        store.entries.push(entry);
+        store.next_id += 1;
+    }
+
+    result
+}
+
+#[tauri::command]
+async fn import_vault(
+    export_data: String,
+    export_password: String,
+    merge_strategy: MergeStrategy,
+    master_password: Option<String>,
+) -> Result<ImportResult, String> {
+    let imported_store = decrypt_export(&export_data, &export_password)?;
+    let mut store = load_password_store(master_password.as_deref())?;
+
+    let result = merge_imported_entries(&mut store, imported_store.entries, merge_strategy);
+    save_password_store(&store, master_password.as_deref())?;
+
+    Ok(result)
+}
+
+// Plain age (x25519) so the file opens with the stock `age -d -i key.txt`
+#[tauri::command]
+async fn export_vault_age(recipient: String, master_password: Option<String>) -> Result<String, String> {
+    use std::io::Write;
+
+    let recipient: age::x25519::Recipient = recipient
+        .trim()
+        .parse()
+        .map_err(|e| format!("Invalid age recipient: {}", e))?;
+    let store = load_password_store(master_password.as_deref())?;
+    let store_json = zeroize::Zeroizing::new(
+        serde_json::to_string(&store).map_err(|e| format!("Failed to serialize store: {}", e))?,
+    );
+
+    let encryptor = age::Encryptor::with_recipients(vec![Box::new(recipient)])
+        .ok_or_else(|| "No age recipient given".to_string())?;
+    let mut armored = Vec::new();
+    let armor = age::armor::ArmoredWriter::wrap_output(&mut armored, age::armor::Format::AsciiArmor)
+        .map_err(|e| format!("Failed to encrypt export: {}", e))?;
+    let mut writer = encryptor
+        .wrap_output(armor)
+        .map_err(|e| format!("Failed to encrypt export: {}", e))?;
+    writer
+        .write_all(store_json.as_bytes())
+        .and_then(|_| writer.finish())
+        .and_then(|armor| armor.finish())
+        .map_err(|e| format!("Failed to encrypt export: {}", e))?;
+
+    String::from_utf8(armored).map_err(|e| format!("Failed to encode export: {}", e))
+}
+
+#[tauri::command]
+async fn import_vault_age(
+    armored: String,
+    identity: String,
+    merge_strategy: Option<MergeStrategy>,
+    master_password: Option<String>,
+) -> Result<ImportResult, String> {
+    use std::io::Read;
+
+    let identity = zeroize::Zeroizing::new(identity);
+    let identity: age::x25519::Identity = identity
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("AGE-SECRET-KEY-"))
+        .ok_or_else(|| "No AGE-SECRET-KEY found in identity".to_string())?
+        .parse()
+        .map_err(|e| format!("Invalid age identity: {}", e))?;
+
+    let decryptor = match age::Decryptor::new(age::armor::ArmoredReader::new(armored.as_bytes()))
+        .map_err(|e| format!("Not an age file: {}", e))?
+    {
+        age::Decryptor::Recipients(decryptor) => decryptor,
+        age::Decryptor::Passphrase(_) => {
+            return Err("Passphrase-encrypted age files are not supported".to_string())
+        }
+    };
+    let mut store_json = zeroize::Zeroizing::new(String::new());
+    decryptor
+        .decrypt(std::iter::once(&identity as &dyn age::Identity))
+        .map_err(|e| format!("Failed to decrypt age file: {}", e))?
+        .read_to_string(&mut store_json)
+        .map_err(|e| format!("Failed to decrypt age file: {}", e))?;
+
+    let imported: PasswordStore = serde_json::from_str(&store_json)
+        .map_err(|e| format!("age file does not contain a Cocoon vault: {}", e))?;
+    let mut store = load_password_store(master_password.as_deref())?;
+
+    let result = merge_imported_entries(
+        &mut store,
+        imported.entries,
+        merge_strategy.unwrap_or(MergeStrategy::Skip),
+    );
+    save_password_store(&store, master_password.as_deref())?;
+
+    Ok(result)
+}
+
+#[derive(serde::Serialize)]
+struct DiffEntry {
+    title: String,
+    username: String,
+    url: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct ChangedEntry {
+    id: u32,
+    title: String,
+    fields: Vec<&'static str>,
+}
+
+#[derive(serde::Serialize, Default)]
+struct VaultDiff {
+    // Only in the other vault
+    added: Vec<DiffEntry>,
+    // Only in this vault
+    removed: Vec<DiffEntry>,
+    changed: Vec<ChangedEntry>,
+}
+
+// Same account across vaults: url+username when there's a URL, otherwise the title
+fn diff_key(entry: &PasswordEntry) -> (String, String) {
+    normalized_identity(entry)
+}
+
+fn changed_fields(ours: &PasswordEntry, theirs: &PasswordEntry) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+    if ours.title != theirs.title {
+        fields.push("title");
+    }
+    if ours.username != theirs.username {
+        fields.push("username");
+    }
+    if ours.password != theirs.password {
+        fields.push("password");
+    }
+    if ours.url != theirs.url {
+        fields.push("url");
+    }
+    if ours.notes != theirs.notes {
+        fields.push("notes");
+    }
+    if ours.totp_secret != theirs.totp_secret {
+        fields.push("totp_secret");
+    }
+    if ours.custom_fields != theirs.custom_fields {
+        fields.push("custom_fields");
+    }
+    fields
+}
+
+// Read-only comparison against an encrypted export, e.g. before import_vault
+#[tauri::command]
+async fn diff_vaults(
+    other_export: String,
+    other_password: String,
+    master_password: Option<String>,
+) -> Result<VaultDiff, String> {
+    let other = decrypt_export(&other_export, &other_password)?;
+    let store = load_password_store(master_password.as_deref())?;
+
+    let mut theirs: HashMap<_, &PasswordEntry> =
+        other.entries.iter().map(|entry| (diff_key(entry), entry)).collect();
+    let mut diff = VaultDiff::default();
+
+    for ours in &store.entries {
+        match theirs.remove(&diff_key(ours)) {
+            Some(other_entry) => {
+                let fields = changed_fields(ours, other_entry);
+                if !fields.is_empty() {
+                    diff.changed.push(ChangedEntry {
+                        id: ours.id,
+                        title: ours.title.clone(),
+                        fields,
+                    });
+                }
+            }
+            None => diff.removed.push(DiffEntry {
+                title: ours.title.clone(),
+                username: ours.username.clone(),
+                url: ours.url.clone(),
+            }),
+        }
+    }
+
+    // Keep the other vault's order for what's left
+    diff.added = other
+        .entries
+        .iter()
+        .filter(|entry| theirs.contains_key(&diff_key(entry)))
+        .map(|entry| DiffEntry {
+            title: entry.title.clone(),
+            username: entry.username.clone(),
+            url: entry.url.clone(),
+        })
+        .collect();
+
+    Ok(diff)
+}
+
+// Generic CSV with a header row: title/name, username/login, password, url, notes
+fn parse_csv_entries(csv_content: &str) -> Result<Vec<PasswordEntry>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(csv_content.as_bytes());
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| format!("Failed to read CSV header: {}", e))?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    let column = |names: &[&str]| headers.iter().position(|h| names.contains(&h.as_str()));
+
+    let title_col = column(&["title", "name"]);
+    let username_col = column(&["username", "login", "user"]);
+    let password_col = column(&["password"]);
+    let url_col = column(&["url", "website", "login_uri"]);
+    let notes_col = column(&["notes", "note", "extra"]);
+
+    if password_col.is_none() {
+        return Err("CSV must have a 'password' column".to_string());
+    }
+
+    let mut entries = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("Failed to read CSV row: {}", e))?;
+        let get = |col: Option<usize>| {
+            col.and_then(|i| record.get(i))
+                .map(|v| v.trim().to_string())
+                .unwrap_or_default()
+        };
+        let optional = |value: String| if value.is_empty() { None } else { Some(value) };
+
+        let username = get(username_col);
+        let password = get(password_col);
+        if username.is_empty() && password.is_empty() {
+            continue;
+        }
+
+        let url = optional(get(url_col));
+        let title = match optional(get(title_col)) {
+            Some(title) => title,
+            None => url.clone().unwrap_or_else(|| username.clone()),
+        };
+
+        entries.push(new_login_entry(
+            title,
+            username,
+            password,
+            url,
+            optional(get(notes_col)),
+        ));
+    }
+
+    Ok(entries)
+}
+
+#[tauri::command]
+async fn import_csv(
+    csv_content: String,
+    merge_strategy: MergeStrategy,
+    master_password: Option<String>,
+) -> Result<ImportResult, String> {
+    let incoming = parse_csv_entries(&csv_content)?;
+    let mut store = load_password_store(master_password.as_deref())?;
+
+    let result = merge_imported_entries(&mut store, incoming, merge_strategy);
+    save_password_store(&store, master_password.as_deref())?;
+
+    Ok(result)
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+enum TotpAlgo {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+    // Steam's 5-character alphanumeric codes (HMAC-SHA1 underneath)
+    SteamGuard,
+}
+
+// Digit counts accepted for standard codes; Steam codes are always 5 characters
+const TOTP_DIGITS: std::ops::RangeInclusive<u8> = 6..=10;
+
+#[derive(serde::Serialize)]
+struct OtpAuthConfig {
+    secret: String,
+    issuer: Option<String>,
+    account: Option<String>,
+    digits: u8,
+    period: u32,
+    algorithm: TotpAlgo,
+}
+
+// Parse `otpauth://totp/Issuer:account?secret=...&digits=..&period=..&algorithm=..`
+// (plus the `steam://SECRET` shorthand some Steam exporters emit)
+fn parse_otpauth(uri: &str) -> Result<OtpAuthConfig, String> {
+    let uri = uri.trim();
+
+    if let Some(secret) = uri.strip_prefix("steam://") {
+        return Ok(OtpAuthConfig {
+            secret: canonical_totp_secret(secret)?,
+            issuer: Some("Steam".to_string()),
+            account: None,
+            digits: 5,
+            period: 30,
+            algorithm: TotpAlgo::SteamGuard,
+        });
+    }
+
+    let parsed = url::Url::parse(uri).map_err(|e| format!("Invalid otpauth URI: {}", e))?;
+    if parsed.scheme() != "otpauth" {
+        return Err("Not an otpauth:// URI".to_string());
+    }
+    if !parsed.host_str().is_some_and(|h| h.eq_ignore_ascii_case("totp")) {
+        return Err("Only TOTP (otpauth://totp/...) is supported".to_string());
+    }
+
+    let label = percent_encoding::percent_decode_str(parsed.path().trim_start_matches('/'))
+        .decode_utf8_lossy()
+        .to_string();
+    let (label_issuer, account) = match label.split_once(':') {
+        Some((issuer, account)) => (Some(issuer.trim().to_string()), account.trim().to_string()),
+        None => (None, label.trim().to_string()),
+    };
+
+    let mut config = OtpAuthConfig {
+        secret: String::new(),
+        issuer: label_issuer,
+        account: if account.is_empty() { None } else { Some(account) },
+        digits: 6,
+        period: 30,
+        algorithm: TotpAlgo::Sha1,
+    };
+    let mut steam_encoder = false;
+
+    for (name, value) in parsed.query_pairs() {
+        match name.to_lowercase().as_str() {
+            "secret" => config.secret = value.to_string(),
+            "issuer" if !value.is_empty() => config.issuer = Some(value.to_string()),
+            "digits" => {
+                config.digits = value
+                    .parse()
+                    .map_err(|_| format!("Invalid digits '{}'", value))?
+            }
+            "period" => {
+                config.period = value
+                    .parse()
+                    .map_err(|_| format!("Invalid period '{}'", value))?
+            }
+            "algorithm" => {
+                config.algorithm = match value.to_uppercase().as_str() {
+                    "SHA1" => TotpAlgo::Sha1,
+                    "SHA256" => TotpAlgo::Sha256,
+                    "SHA512" => TotpAlgo::Sha512,
+                    other => return Err(format!("Unsupported TOTP algorithm '{}'", other)),
+                }
+            }
+            "encoder" => steam_encoder = value.eq_ignore_ascii_case("steam"),
+            _ => {}
+        }
+    }
+
+    let is_steam = steam_encoder
+        || config
+            .issuer
+            .as_deref()
+            .is_some_and(|issuer| issuer.eq_ignore_ascii_case("steam"));
+    if is_steam {
+        config.algorithm = TotpAlgo::SteamGuard;
+        config.digits = 5;
+    }
+
+    if config.secret.trim().is_empty() {
+        return Err("otpauth URI has no secret".to_string());
+    }
+    config.secret = canonical_totp_secret(&config.secret)?;
+    if !is_steam && !TOTP_DIGITS.contains(&config.digits) {
+        return Err(format!(
+            "TOTP digits must be between {} and {}, got {}",
+            TOTP_DIGITS.start(),
+            TOTP_DIGITS.end(),
+            config.digits
+        ));
+    }
+    if config.period == 0 {
+        return Err("TOTP period must be greater than zero".to_string());
+    }
+
+    Ok(config)
+}
+
+// TOTP configuration as sent by the entry editor
+#[derive(serde::Deserialize)]
+struct TotpSettings {
+    secret: String,
+    #[serde(default = "default_totp_digits")]
+    digits: u8,
+    #[serde(default = "default_totp_period")]
+    period: u32,
+    #[serde(default)]
+    algorithm: TotpAlgo,
+}
+
+impl TotpSettings {
+    fn apply_to(self, entry: &mut PasswordEntry) {
+        entry.totp_secret = Some(self.secret);
+        entry.totp_digits = self.digits;
+        entry.totp_period = self.period;
+        entry.totp_algorithm = self.algorithm;
+    }
+}
+
+impl From<OtpAuthConfig> for TotpSettings {
+    fn from(config: OtpAuthConfig) -> Self {
+        Self {
+            secret: config.secret,
+            digits: config.digits,
+            period: config.period,
+            algorithm: config.algorithm,
+        }
+    }
+}
+
+// Canonical form: uppercase base32 with no spaces, hyphens or `=` padding
+fn canonical_totp_secret(raw: &str) -> Result<String, String> {
+    let secret: String = raw
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .trim_end_matches('=')
+        .to_uppercase();
+
+    if secret.is_empty() {
+        return Err("TOTP secret must not be empty".to_string());
+    }
+    if let Some(c) = secret.chars().find(|c| !matches!(c, 'A'..='Z' | '2'..='7')) {
+        return Err(format!(
+            "TOTP secret contains '{}', which is not a base32 character (A-Z, 2-7)",
+            c
+        ));
+    }
+    // Lengths that leave 1, 3 or 6 characters over can't come from whole bytes
+    if matches!(secret.len() % 8, 1 | 3 | 6) {
+        return Err("TOTP secret looks truncated: its length is not valid base32".to_string());
+    }
+
+    decode_totp_secret(&secret)?;
+    Ok(secret)
+}
+
+#[tauri::command]
+async fn normalize_totp_secret(raw: String) -> Result<String, String> {
+    canonical_totp_secret(&raw)
+}
+
+fn validate_totp_settings(mut totp: TotpSettings) -> Result<TotpSettings, String> {
+    totp.secret = canonical_totp_secret(&totp.secret)?;
+
+    if totp.algorithm == TotpAlgo::SteamGuard {
+        // Steam codes are always 5 characters
+        totp.digits = 5;
+    } else if !TOTP_DIGITS.contains(&totp.digits) {
+        return Err(format!(
+            "TOTP digits must be between {} and {}, got {}",
+            TOTP_DIGITS.start(),
+            TOTP_DIGITS.end(),
+            totp.digits
+        ));
+    }
+    if !(1..=300).contains(&totp.period) {
+        return Err(format!("TOTP period must be between 1 and 300 seconds, got {}", totp.period));
+    }
+    Ok(totp)
+}
+
+fn decode_totp_secret(secret: &str) -> Result<Vec<u8>, String> {
+    let bytes = data_encoding::BASE32_NOPAD
+        .decode(secret.as_bytes())
+        .map_err(|e| format!("Invalid TOTP secret: {}", e))?;
+    if bytes.is_empty() {
+        return Err("TOTP secret must not be empty".to_string());
+    }
+    Ok(bytes)
+}
+
+const STEAM_GUARD_ALPHABET: &[u8] = b"23456789BCDFGHJKMNPQRTVWXY";
+
+// RFC 6238 with dynamic truncation (RFC 4226 §5.3)
+fn totp_code(
+    secret: &[u8],
+    digits: u8,
+    period: u32,
+    algorithm: TotpAlgo,
+    unix_time: u64,
+) -> Result<String, String> {
+    use hmac::{Hmac, Mac};
+
+    let counter = (unix_time / u64::from(period)).to_be_bytes();
+    let digest = match algorithm {
+        TotpAlgo::Sha1 | TotpAlgo::SteamGuard => {
+            let mut mac = Hmac::<sha1::Sha1>::new_from_slice(secret).map_err(|e| e.to_string())?;
+            mac.update(&counter);
+            mac.finalize().into_bytes().to_vec()
+        }
+        TotpAlgo::Sha256 => {
+            let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret).map_err(|e| e.to_string())?;
+            mac.update(&counter);
+            mac.finalize().into_bytes().to_vec()
+        }
+        TotpAlgo::Sha512 => {
+            let mut mac = Hmac::<sha2::Sha512>::new_from_slice(secret).map_err(|e| e.to_string())?;
+            mac.update(&counter);
+            mac.finalize().into_bytes().to_vec()
+        }
+    };
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let mut value = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    if algorithm == TotpAlgo::SteamGuard {
+        let alphabet_len = STEAM_GUARD_ALPHABET.len() as u32;
+        let mut code = String::with_capacity(5);
+        for _ in 0..5 {
+            code.push(STEAM_GUARD_ALPHABET[(value % alphabet_len) as usize] as char);
+            value /= alphabet_len;
+        }
+        return Ok(code);
+    }
+
+    let modulus = 10u64.pow(u32::from(digits));
+    Ok(format!(
+        "{:0width$}",
+        u64::from(value) % modulus,
+        width = digits as usize
+    ))
+}
+
+#[derive(serde::Serialize)]
+struct TotpCode {
+    code: String,
+    period: u32,
+    seconds_remaining: u32,
+}
+
+#[tauri::command]
+async fn get_totp_code(entry_id: u32, master_password: Option<String>) -> Result<TotpCode, String> {
+    let entry =
+        find_entry(entry_id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+    let secret = entry
+        .totp_secret
+        .as_deref()
+        .ok_or_else(|| "Entry has no TOTP secret".to_string())?;
+    let secret = zeroize::Zeroizing::new(decode_totp_secret(secret)?);
+
+    let now = chrono::Utc::now().timestamp().max(0) as u64;
+    let period = entry.totp_period.max(1);
+    let code = totp_code(&secret, entry.totp_digits, period, entry.totp_algorithm, now)?;
+
+    Ok(TotpCode {
+        code,
+        period,
+        seconds_remaining: period - (now % u64::from(period)) as u32,
+    })
+}
+
+#[tauri::command]
+async fn parse_otpauth_uri(uri: String) -> Result<OtpAuthConfig, String> {
+    parse_otpauth(&uri)
+}
+
+fn build_otpauth_uri(entry: &PasswordEntry, secret: &str) -> String {
+    use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+    let issuer = utf8_percent_encode(&entry.title, NON_ALPHANUMERIC).to_string();
+    let account = utf8_percent_encode(&entry.username, NON_ALPHANUMERIC).to_string();
+    let algorithm = match entry.totp_algorithm {
+        TotpAlgo::Sha1 => "SHA1",
+        TotpAlgo::Sha256 => "SHA256",
+        TotpAlgo::Sha512 => "SHA512",
+        TotpAlgo::SteamGuard => return format!("steam://{}", secret),
+    };
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&digits={}&period={}&algorithm={}",
+        issuer, account, secret, issuer, entry.totp_digits, entry.totp_period, algorithm
+    )
+}
+
+// PNG data URI of the entry's otpauth:// URI, for re-provisioning another authenticator
+#[tauri::command]
+async fn totp_setup_qr(entry_id: u32, master_password: Option<String>) -> Result<String, String> {
+    let entry =
+        find_entry(entry_id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+    let secret = entry
+        .totp_secret
+        .as_deref()
+        .ok_or_else(|| "Entry has no TOTP secret".to_string())?;
+
+    let uri = zeroize::Zeroizing::new(build_otpauth_uri(&entry, secret));
+    let code = qrcode::QrCode::new(uri.as_bytes())
+        .map_err(|e| format!("Failed to build QR code: {}", e))?;
+    let image = code
+        .render::<image::Luma<u8>>()
+        .min_dimensions(256, 256)
+        .build();
+
+    let mut png = Vec::new();
+    image::DynamicImage::ImageLuma8(image)
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode QR code: {}", e))?;
+
+    Ok(format!(
+        "data:image/png;base64,{}",
+        general_purpose::STANDARD.encode(png)
+    ))
+}
+
+#[tauri::command]
+async fn decode_totp_from_qr(image_bytes: Vec<u8>) -> Result<OtpAuthConfig, String> {
+    let image = image::load_from_memory(&image_bytes)
+        .map_err(|e| format!("Failed to read image: {}", e))?
+        .to_luma8();
+
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(
+        image.width() as usize,
+        image.height() as usize,
+        |x, y| image.get_pixel(x as u32, y as u32).0[0],
+    );
+    let grids = prepared.detect_grids();
+    let grid = grids.first().ok_or("No QR code found in image")?;
+    let (_, content) = grid
+        .decode()
+        .map_err(|e| format!("Failed to decode QR code: {}", e))?;
+
+    parse_otpauth(&content)
+}
+
+#[derive(serde::Deserialize, Clone, Copy)]
+enum BrowserKind {
+    Chrome,
+    Firefox,
+}
+
+#[derive(serde::Serialize)]
+struct MalformedRow {
+    line: u64,
+    reason: String,
+}
+
+#[derive(serde::Serialize, Default)]
+struct BrowserImportResult {
+    added: usize,
+    skipped: usize,
+    malformed: Vec<MalformedRow>,
+}
+
+// Chrome: name,url,username,password[,note]
+// Firefox: url,username,password,httpRealm,formActionOrigin,guid,...
+fn parse_browser_csv(
+    csv_content: &str,
+    browser: BrowserKind,
+) -> Result<(Vec<PasswordEntry>, Vec<MalformedRow>), String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(csv_content.as_bytes());
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| format!("Failed to read CSV header: {}", e))?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    let column = |name: &str| headers.iter().position(|h| h == name);
+
+    let (name_col, notes_col) = match browser {
+        BrowserKind::Chrome => (column("name"), column("note")),
+        BrowserKind::Firefox => (None, None),
+    };
+    let (url_col, username_col, password_col) =
+        match (column("url"), column("username"), column("password")) {
+            (Some(url), Some(username), Some(password)) => (url, username, password),
+            _ => return Err("Not a browser export: expected url, username and password columns".to_string()),
+        };
+
+    let mut malformed = Vec::new();
+    let mut incoming = Vec::new();
+
+    for (index, record) in reader.records().enumerate() {
+        // Header is line 1
+        let line = index as u64 + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                malformed.push(MalformedRow {
+                    line,
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let get = |col: usize| {
+            record
+                .get(col)
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+
+        let password = match get(password_col) {
+            Some(password) => password,
+            None => {
+                malformed.push(MalformedRow {
+                    line,
+                    reason: "Missing password".to_string(),
+                });
+                continue;
+            }
+        };
+        let username = get(username_col).unwrap_or_default();
+        let url = get(url_col);
+        let title = name_col
+            .and_then(get)
+            .or_else(|| url.as_deref().and_then(registrable_domain))
+            .or_else(|| url.clone())
+            .unwrap_or_else(|| username.clone());
+
+        incoming.push(new_login_entry(
+            title,
+            username,
+            password,
+            url,
+            notes_col.and_then(get),
+        ));
+    }
+
+    Ok((incoming, malformed))
+}
+
+#[tauri::command]
+async fn import_browser_csv(
+    csv_content: String,
+    browser: BrowserKind,
+    master_password: Option<String>,
+) -> Result<BrowserImportResult, String> {
+    let (incoming, malformed) = parse_browser_csv(&csv_content, browser)?;
+
+    let mut store = load_password_store(master_password.as_deref())?;
+    let merged = merge_imported_entries(&mut store, incoming, MergeStrategy::Skip);
+
+    if merged.added > 0 {
+        save_password_store(&store, master_password.as_deref())?;
+    }
+
+    Ok(BrowserImportResult {
+        added: merged.added,
+        skipped: merged.skipped,
+        malformed,
+    })
+}
+
+// JSON keys holding each field; title and password are required per item
+#[derive(serde::Deserialize)]
+#[serde(default)]
+struct FieldMapping {
+    title: String,
+    username: String,
+    password: String,
+    url: Option<String>,
+    notes: Option<String>,
+}
+
+impl Default for FieldMapping {
+    fn default() -> Self {
+        Self {
+            title: "title".to_string(),
+            username: "username".to_string(),
+            password: "password".to_string(),
+            url: Some("url".to_string()),
+            notes: Some("notes".to_string()),
+        }
+    }
+}
+
+const MAX_REPORTED_JSON_ERRORS: usize = 5;
+
+#[derive(serde::Serialize)]
+struct JsonImportError {
+    index: usize,
+    reason: String,
+}
+
+#[derive(serde::Serialize)]
+struct JsonImportResult {
+    added: usize,
+    skipped: usize,
+    failed: usize,
+    errors: Vec<JsonImportError>,
+}
+
+// Strings are taken as-is and numbers/bools stringified; null, arrays and
+// objects count as missing
+fn json_field(item: &serde_json::Map<String, serde_json::Value>, key: &str) -> Option<String> {
+    match item.get(key)? {
+        serde_json::Value::String(value) => Some(value.trim().to_string()),
+        serde_json::Value::Number(value) => Some(value.to_string()),
+        serde_json::Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+    .filter(|value| !value.is_empty())
+}
+
+fn json_item_entry(item: &serde_json::Value, mapping: &FieldMapping) -> Result<PasswordEntry, String> {
+    let item = item.as_object().ok_or("Not a JSON object")?;
+    let title = json_field(item, &mapping.title)
+        .ok_or_else(|| format!("Missing \"{}\"", mapping.title))?;
+    let password = json_field(item, &mapping.password)
+        .ok_or_else(|| format!("Missing \"{}\"", mapping.password))?;
+
+    Ok(new_login_entry(
+        title,
+        json_field(item, &mapping.username).unwrap_or_default(),
+        password,
+        mapping.url.as_deref().and_then(|key| json_field(item, key)),
+        mapping.notes.as_deref().and_then(|key| json_field(item, key)),
+    ))
+}
+
+#[tauri::command]
+async fn import_json(
+    json_content: String,
+    mapping: Option<FieldMapping>,
+    master_password: Option<String>,
+) -> Result<JsonImportResult, String> {
+    let mapping = mapping.unwrap_or_default();
+    let items: Vec<serde_json::Value> = serde_json::from_str(&json_content)
+        .map_err(|e| format!("Failed to parse JSON array: {}", e))?;
+
+    let mut incoming = Vec::new();
+    let mut errors = Vec::new();
+    let mut failed = 0;
+    for (index, item) in items.iter().enumerate() {
+        match json_item_entry(item, &mapping) {
+            Ok(entry) => incoming.push(entry),
+            Err(reason) => {
+                failed += 1;
+                if errors.len() < MAX_REPORTED_JSON_ERRORS {
+                    errors.push(JsonImportError { index, reason });
+                }
+            }
+        }
+    }
+
+    let mut store = load_password_store(master_password.as_deref())?;
+    let merged = merge_imported_entries(&mut store, incoming, MergeStrategy::Skip);
+    if merged.added > 0 {
+        save_password_store(&store, master_password.as_deref())?;
+    }
+
+    Ok(JsonImportResult {
+        added: merged.added,
+        skipped: merged.skipped,
+        failed,
+        errors,
+    })
+}
+
+#[derive(serde::Deserialize, Clone, Copy)]
+enum ImportFormat {
+    Csv,
+    Chrome,
+    Firefox,
+    Bitwarden,
+}
+
+// Unencrypted Bitwarden JSON export; only login items (type 1) are taken
+fn parse_bitwarden_json(content: &str) -> Result<(Vec<PasswordEntry>, Vec<MalformedRow>), String> {
+    let export: serde_json::Value =
+        serde_json::from_str(content).map_err(|e| format!("Failed to parse Bitwarden export: {}", e))?;
+    if export.get("encrypted").and_then(|v| v.as_bool()) == Some(true) {
+        return Err("Encrypted Bitwarden exports are not supported".to_string());
+    }
+    let items = export
+        .get("items")
+        .and_then(|v| v.as_array())
+        .ok_or("Not a Bitwarden export: missing 'items'")?;
+
+    let text = |value: Option<&serde_json::Value>| {
+        value
+            .and_then(|v| v.as_str())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+
+    let mut entries = Vec::new();
+    let mut malformed = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        // Items are reported 1-based, like rows in a CSV
+        let line = index as u64 + 1;
+        if item.get("type").and_then(|v| v.as_u64()) != Some(1) {
+            continue;
+        }
+        let login = match item.get("login") {
+            Some(login) => login,
+            None => {
+                malformed.push(MalformedRow {
+                    line,
+                    reason: "Login item has no login data".to_string(),
+                });
+                continue;
+            }
+        };
+        let password = match text(login.get("password")) {
+            Some(password) => password,
+            None => {
+                malformed.push(MalformedRow {
+                    line,
+                    reason: "Missing password".to_string(),
+                });
+                continue;
+            }
+        };
+
+        let username = text(login.get("username")).unwrap_or_default();
+        let url = login
+            .get("uris")
+            .and_then(|v| v.as_array())
+            .and_then(|uris| uris.first())
+            .and_then(|uri| text(uri.get("uri")));
+        let title = text(item.get("name"))
+            .or_else(|| url.clone())
+            .unwrap_or_else(|| username.clone());
+
+        // Stored secrets must be canonical, or the entry could never be edited
+        let totp = match text(login.get("totp")) {
+            Some(totp) if totp.starts_with("otpauth://") || totp.starts_with("steam://") => Some(
+                parse_otpauth(&totp)
+                    .map(TotpSettings::from)
+                    .and_then(validate_totp_settings),
+            ),
+            Some(secret) => Some(validate_totp_settings(TotpSettings {
+                secret,
+                digits: default_totp_digits(),
+                period: default_totp_period(),
+                algorithm: TotpAlgo::default(),
+            })),
+            None => None,
+        };
+        let totp = match totp.transpose() {
+            Ok(totp) => totp,
+            Err(e) => {
+                malformed.push(MalformedRow {
+                    line,
+                    reason: format!("Invalid TOTP: {}", e),
+                });
+                continue;
+            }
+        };
+
+        let mut entry = new_login_entry(title, username, password, url, text(item.get("notes")));
+        if let Some(totp) = totp {
+            totp.apply_to(&mut entry);
+        }
+        entries.push(entry);
+    }
+
+    Ok((entries, malformed))
+}
+
+fn parse_import(
+    content: &str,
+    format: ImportFormat,
+) -> Result<(Vec<PasswordEntry>, Vec<MalformedRow>), String> {
+    match format {
+        ImportFormat::Csv => Ok((parse_csv_entries(content)?, Vec::new())),
+        ImportFormat::Chrome => parse_browser_csv(content, BrowserKind::Chrome),
+        ImportFormat::Firefox => parse_browser_csv(content, BrowserKind::Firefox),
+        ImportFormat::Bitwarden => parse_bitwarden_json(content),
+    }
+}
+
+const IMPORT_PREVIEW_TTL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+struct PendingImport {
+    entries: Vec<PasswordEntry>,
+    expires_at: std::time::Instant,
+}
+
+lazy_static::lazy_static! {
+    // Parsed-but-not-applied imports, keyed by the token handed to the UI
+    static ref PENDING_IMPORTS: Mutex<HashMap<String, PendingImport>> = Mutex::new(HashMap::new());
+}
+
+#[derive(serde::Serialize)]
+struct ImportPreviewEntry {
+    title: String,
+    username: String,
+    url: Option<String>,
+    duplicate: bool,
+}
+
+#[derive(serde::Serialize)]
+struct ImportPreview {
+    token: String,
+    entries: Vec<ImportPreviewEntry>,
+    new_count: usize,
+    duplicate_count: usize,
+    malformed: Vec<MalformedRow>,
+}
+
+// Read-only: nothing is written until commit_import is called with the token
+#[tauri::command]
+async fn preview_import(
+    content: String,
+    format: ImportFormat,
+    master_password: Option<String>,
+) -> Result<ImportPreview, String> {
+    let (incoming, malformed) = parse_import(&content, format)?;
+    let store = load_password_store(master_password.as_deref())?;
+
+    let existing: std::collections::HashSet<(String, String)> =
+        store.entries.iter().map(normalized_identity).collect();
+    let entries: Vec<ImportPreviewEntry> = incoming
+        .iter()
+        .map(|entry| ImportPreviewEntry {
+            title: entry.title.clone(),
+            username: entry.username.clone(),
+            url: entry.url.clone(),
+            duplicate: existing.contains(&normalized_identity(entry)),
+        })
+        .collect();
+    let duplicate_count = entries.iter().filter(|entry| entry.duplicate).count();
+
+    let token = generate_token();
+    let mut pending = PENDING_IMPORTS.lock().unwrap();
+    let now = std::time::Instant::now();
+    pending.retain(|_, import| import.expires_at > now);
+    pending.insert(
+        token.clone(),
+        PendingImport {
+            entries: incoming,
+            expires_at: now + IMPORT_PREVIEW_TTL,
+        },
+    );
+
+    Ok(ImportPreview {
+        token,
+        new_count: entries.len() - duplicate_count,
+        duplicate_count,
+        entries,
+        malformed,
+    })
+}
+
+#[tauri::command]
+async fn commit_import(
+    token: String,
+    merge_strategy: MergeStrategy,
+    master_password: Option<String>,
+) -> Result<ImportResult, String> {
+    let pending = PENDING_IMPORTS
+        .lock()
+        .unwrap()
+        .remove(&token)
+        .ok_or_else(|| "Import preview is invalid or already applied".to_string())?;
+
+    if pending.expires_at <= std::time::Instant::now() {
+        return Err("Import preview has expired; preview the file again".to_string());
+    }
+
+    let mut store = load_password_store(master_password.as_deref())?;
+    let result = merge_imported_entries(&mut store, pending.entries, merge_strategy);
+    save_password_store(&store, master_password.as_deref())?;
+
+    Ok(result)
+}
+
+#[derive(serde::Serialize, Default)]
+struct AppleImportResult {
+    imported: usize,
+    skipped: usize,
+    totp_recovered: usize,
+}
+
+// Apple Passwords / iCloud Keychain: Title,URL,Username,Password,Notes,OTPAuth
+#[tauri::command]
+async fn import_apple_passwords(
+    csv_content: String,
+    master_password: Option<String>,
+) -> Result<AppleImportResult, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(csv_content.as_bytes());
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| format!("Failed to read CSV header: {}", e))?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    let column = |name: &str| headers.iter().position(|h| h == name);
+
+    let title_col = column("title");
+    let url_col = column("url");
+    let username_col = column("username");
+    let password_col = column("password");
+    let notes_col = column("notes");
+    let otpauth_col = column("otpauth");
+
+    if password_col.is_none() || username_col.is_none() {
+        return Err("Not an Apple Passwords export: missing Username/Password columns".to_string());
+    }
+
+    let mut store = load_password_store(master_password.as_deref())?;
+    let mut result = AppleImportResult::default();
+
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("Failed to read CSV row: {}", e))?;
+        let get = |col: Option<usize>| {
+            col.and_then(|i| record.get(i))
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+
+        let username = get(username_col).unwrap_or_default();
+        let password = get(password_col).unwrap_or_default();
+        if username.is_empty() && password.is_empty() {
+            result.skipped += 1;
+            continue;
+        }
+
+        let url = get(url_col);
+        let title = get(title_col)
+            .or_else(|| url.clone())
+            .unwrap_or_else(|| username.clone());
+
+        let mut entry = new_login_entry(title, username, password, url, get(notes_col));
+        let totp = get(otpauth_col).and_then(|uri| {
+            parse_otpauth(&uri)
+                .map(TotpSettings::from)
+                .and_then(validate_totp_settings)
+                .ok()
+        });
+        if let Some(totp) = totp {
+            totp.apply_to(&mut entry);
+        }
+        if entry.totp_secret.is_some() {
+            result.totp_recovered += 1;
+        }
+
+        entry.id = store.next_id;
+        store.entries.push(entry);
+        store.next_id += 1;
+        result.imported += 1;
+    }
+
+    save_password_store(&store, master_password.as_deref())?;
+
+    Ok(result)
+}
+
+// Binary search over a sorted `HASH[:count]` line file (HIBP "ordered by hash" format)
+fn sorted_hashset_contains(data: &[u8], target: &[u8]) -> bool {
+    let (mut lo, mut hi) = (0usize, data.len());
+
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        let start = data[..mid]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |p| p + 1);
+        let end = data[start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(data.len(), |p| start + p);
+
+        let line = &data[start..end];
+        let hash = line
+            .split(|&b| b == b':')
+            .next()
+            .unwrap_or(line)
+            .trim_ascii();
+
+        match hash
+            .iter()
+            .map(u8::to_ascii_uppercase)
+            .cmp(target.iter().copied())
+        {
+            std::cmp::Ordering::Equal => return true,
+            std::cmp::Ordering::Less => lo = end + 1,
+            std::cmp::Ordering::Greater => hi = start,
+        }
+    }
+
+    false
+}
+
+// Offline breach check: never touches the network, the hash file is user-supplied
+#[tauri::command]
+async fn scan_vault_against_hashset(
+    hashset_path: String,
+    master_password: Option<String>,
+) -> Result<Vec<u32>, String> {
+    use sha1::{Digest, Sha1};
+
+    let file = fs::File::open(&hashset_path)
+        .map_err(|e| format!("Failed to open hash set: {}", e))?;
+    // SAFETY: the map is read-only and only lives for the duration of this scan
+    let hashset = unsafe { memmap2::Mmap::map(&file) }
+        .map_err(|e| format!("Failed to map hash set: {}", e))?;
+
+    let store = load_password_store(master_password.as_deref())?;
+
+    let compromised = store
+        .entries
+        .iter()
+        .filter(|entry| !entry.password.is_empty())
+        .filter(|entry| {
+            let digest = Sha1::digest(entry.password.as_bytes());
+            let hash_hex: String = digest.iter().map(|b| format!("{:02X}", b)).collect();
+            sorted_hashset_contains(&hashset, hash_hex.as_bytes())
+        })
+        .map(|entry| entry.id)
+        .collect();
+
+    Ok(compromised)
+}
+
+#[cfg(target_os = "windows")]
+fn get_windows_hello_blob_path() -> Result<PathBuf, String> {
+    Ok(get_app_data_dir()?.join("hello.key"))
+}
+
+// DPAPI ties the blob to the current Windows user account
+#[cfg(target_os = "windows")]
+fn dpapi_transform(data: &[u8], protect: bool) -> Result<Vec<u8>, String> {
+    use std::ptr;
+    use winapi::um::dpapi::{CryptProtectData, CryptUnprotectData};
+    use winapi::um::winbase::LocalFree;
+    use winapi::um::wincrypt::DATA_BLOB;
+
+    unsafe {
+        let mut input = DATA_BLOB {
+            cbData: data.len() as u32,
+            pbData: data.as_ptr() as *mut u8,
+        };
+        let mut output: DATA_BLOB = std::mem::zeroed();
+
+        let ok = if protect {
+            CryptProtectData(
+                &mut input,
+                ptr::null(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                0,
+                &mut output,
+            )
+        } else {
+            CryptUnprotectData(
+                &mut input,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                0,
+                &mut output,
+            )
+        };
+
+        if ok == 0 {
+            return Err("DPAPI operation failed".to_string());
+        }
+
+        let bytes = std::slice::from_raw_parts(output.pbData, output.cbData as usize).to_vec();
+        LocalFree(output.pbData as _);
+        Ok(bytes)
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn windows_hello_available() -> bool {
+    use windows::Security::Credentials::UI::{
+        UserConsentVerifier, UserConsentVerifierAvailability,
+    };
+
+    UserConsentVerifier::CheckAvailabilityAsync()
+        .and_then(|op| op.get())
+        .map(|availability| availability == UserConsentVerifierAvailability::Available)
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "windows")]
+fn windows_hello_verify(message: &str) -> Result<(), String> {
+    use windows::core::HSTRING;
+    use windows::Security::Credentials::UI::{UserConsentVerificationResult, UserConsentVerifier};
+
+    let result = UserConsentVerifier::RequestVerificationAsync(&HSTRING::from(message))
+        .and_then(|op| op.get())
+        .map_err(|e| format!("Windows Hello prompt failed: {}", e))?;
+
+    if result == UserConsentVerificationResult::Verified {
+        Ok(())
+    } else {
+        Err("Windows Hello verification was not completed".to_string())
+    }
+}
+
+// Capability probe so the UI only offers Hello where it can work
+#[tauri::command]
+async fn windows_hello_status() -> Result<bool, String> {
+    #[cfg(target_os = "windows")]
+    {
+        Ok(windows_hello_available())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        Ok(false)
+    }
+}
+
+#[tauri::command]
+async fn enable_windows_hello_unlock(master_password: String) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        if !windows_hello_available() {
+            return Err("Windows Hello is not configured on this device".to_string());
+        }
+
+        let key = zeroize::Zeroizing::new(derive_master_key(&master_password)?);
+        windows_hello_verify("Enable Windows Hello unlock for Cocoon")?;
+
+        let blob = dpapi_transform(&key, true)?;
+        fs::write(
+            get_windows_hello_blob_path()?,
+            general_purpose::STANDARD.encode(blob),
+        )
+        .map_err(|e| format!("Failed to save Windows Hello key: {}", e))
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = master_password;
+        Err("Windows Hello is only available on Windows".to_string())
+    }
+}
+
+#[tauri::command]
+async fn unlock_with_windows_hello() -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        let blob_path = get_windows_hello_blob_path()?;
+        if !blob_path.exists() {
+            return Err("Windows Hello unlock is not enabled".to_string());
+        }
+
+        windows_hello_verify("Unlock Cocoon")?;
+
+        let encoded = fs::read_to_string(&blob_path)
+            .map_err(|e| format!("Failed to read Windows Hello key: {}", e))?;
+        let blob = general_purpose::STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| format!("Failed to decode Windows Hello key: {}", e))?;
+        let key = zeroize::Zeroizing::new(dpapi_transform(&blob, false)?);
+
+        // A master password change leaves a stale key behind; drop it
+        let encrypted_store = load_encrypted_store()?;
+        if decrypt_bytes(&encrypted_store.encrypted_data, &encrypted_store.nonce, &key).is_err() {
+            let _ = fs::remove_file(&blob_path);
+            return Err("Stored Windows Hello key is out of date; unlock with your master password".to_string());
+        }
+
+        start_session(&key, "windows_hello");
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        Err("Windows Hello is only available on Windows".to_string())
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum IntegrityStatus {
+    Intact,
+    Modified,
+    // No signature scheme to check against on this platform
+    Unsupported,
+}
+
+#[derive(serde::Serialize)]
+struct IntegrityCheck {
+    status: IntegrityStatus,
+    method: &'static str,
+    detail: Option<String>,
+}
+
+// Verify the whole .app bundle when running from one, so swapped resources count too
+#[cfg(target_os = "macos")]
+fn check_app_signature(exe: &std::path::Path) -> Result<IntegrityCheck, String> {
+    let target = exe
+        .ancestors()
+        .find(|path| path.extension().is_some_and(|ext| ext == "app"))
+        .unwrap_or(exe);
+
+    let output = std::process::Command::new("/usr/bin/codesign")
+        .args(["--verify", "--deep", "--strict"])
+        .arg(target)
+        .output()
+        .map_err(|e| format!("Failed to run codesign: {}", e))?;
+
+    let detail = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Ok(IntegrityCheck {
+        status: if output.status.success() {
+            IntegrityStatus::Intact
+        } else {
+            IntegrityStatus::Modified
+        },
+        method: "codesign",
+        detail: (!detail.is_empty()).then_some(detail),
+    })
+}
+
+#[cfg(target_os = "windows")]
+fn check_app_signature(exe: &std::path::Path) -> Result<IntegrityCheck, String> {
+    // Single quotes are doubled to escape them inside the PowerShell literal
+    let script = format!(
+        "(Get-AuthenticodeSignature -LiteralPath '{}').Status",
+        exe.display().to_string().replace('\'', "''")
+    );
+    let output = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .output()
+        .map_err(|e| format!("Failed to check Authenticode signature: {}", e))?;
+
+    let status = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(IntegrityCheck {
+        status: if status == "Valid" {
+            IntegrityStatus::Intact
+        } else {
+            IntegrityStatus::Modified
+        },
+        method: "authenticode",
+        detail: Some(status),
+    })
+}
+
+// Linux builds aren't signed, and a hash shipped next to the binary could be
+// replaced along with it
+#[cfg(target_os = "linux")]
+fn check_app_signature(_exe: &std::path::Path) -> Result<IntegrityCheck, String> {
+    Ok(IntegrityCheck {
+        status: IntegrityStatus::Unsupported,
+        method: "none",
+        detail: Some("Binary signatures are not available on Linux".to_string()),
+    })
+}
+
+// Meant to run before the master password is typed. Unsigned development
+// builds report as modified.
+#[tauri::command]
+async fn verify_app_integrity() -> Result<IntegrityCheck, String> {
+    let exe = std::env::current_exe()
+        .map_err(|e| format!("Failed to locate the running executable: {}", e))?;
+    let check = check_app_signature(&exe)?;
+
+    if matches!(check.status, IntegrityStatus::Modified) {
+        tracing::warn!(method = check.method, "App signature check failed");
+    }
+    Ok(check)
+}
+
+const MIN_PIN_LENGTH: usize = 4;
+const MAX_PIN_LENGTH: usize = 32;
+const MAX_PIN_ATTEMPTS: u32 = 5;
+
+// A PIN has far less entropy than a master password, so the wrapped key only
+// ever lives in this process: it re-unlocks after an idle or manual lock, but
+// a restart (or panic lock) needs the master password again. There's nothing
+// on disk to brute-force offline or to reset the attempt counter with.
+fn quick_unlock_params() -> argon2::Params {
+    argon2::Params::new(64 * 1024, 3, 1, Some(32)).expect("valid Argon2 parameters")
+}
+
+// Vault key wrapped with a PIN-derived key
+struct QuickUnlock {
+    salt: [u8; 16],
+    encrypted_key: String,
+    nonce: String,
+    failed_attempts: u32,
+}
+
+lazy_static::lazy_static! {
+    // Held for the whole of an unlock attempt, so concurrent calls can't each
+    // get a guess in before the counter moves
+    static ref QUICK_UNLOCK: Mutex<Option<QuickUnlock>> = Mutex::new(None);
+}
+
+fn validate_pin(pin: &str) -> Result<(), String> {
+    let length = pin.chars().count();
+    if !(MIN_PIN_LENGTH..=MAX_PIN_LENGTH).contains(&length) {
+        return Err(format!(
+            "PIN must be between {} and {} characters",
+            MIN_PIN_LENGTH, MAX_PIN_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn enable_quick_unlock(pin: String, master_password: String) -> Result<(), String> {
+    validate_pin(&pin)?;
+    let key = zeroize::Zeroizing::new(derive_master_key(&master_password)?);
+
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let pin_key = zeroize::Zeroizing::new(generate_key_with_params(&pin, &salt, quick_unlock_params())?);
+    let (encrypted_key, nonce) = encrypt_bytes(&key, &pin_key)?;
+
+    *QUICK_UNLOCK.lock().unwrap() = Some(QuickUnlock {
+        salt,
+        encrypted_key,
+        nonce,
+        failed_attempts: 0,
+    });
+    tracing::info!("Quick unlock enabled");
+    Ok(())
+}
+
+#[tauri::command]
+async fn disable_quick_unlock() -> Result<(), String> {
+    QUICK_UNLOCK.lock().unwrap().take();
+    Ok(())
+}
+
+#[tauri::command]
+async fn quick_unlock_enabled() -> Result<bool, String> {
+    Ok(QUICK_UNLOCK.lock().unwrap().is_some())
+}
+
+// Hitting the attempt limit drops the wrapped key and the full master
+// password is required
+#[tauri::command]
+async fn unlock_with_pin(pin: String) -> Result<(), String> {
+    let mut quick_unlock = QUICK_UNLOCK.lock().unwrap();
+    let wrapped = quick_unlock
+        .as_mut()
+        .ok_or_else(|| "Quick unlock is not enabled".to_string())?;
+
+    let pin_key = zeroize::Zeroizing::new(generate_key_with_params(&pin, &wrapped.salt, quick_unlock_params())?);
+    let key = match decrypt_bytes(&wrapped.encrypted_key, &wrapped.nonce, &pin_key) {
+        Ok(key) => zeroize::Zeroizing::new(key),
+        Err(_) => {
+            wrapped.failed_attempts += 1;
+            record_unlock_attempt(false, "pin");
+            if wrapped.failed_attempts >= MAX_PIN_ATTEMPTS {
+                quick_unlock.take();
+                tracing::warn!("Too many wrong PINs, quick unlock disabled");
+                return Err("Too many wrong PINs; unlock with your master password".to_string());
+            }
+            return Err(format!(
+                "Invalid PIN ({} attempts left)",
+                MAX_PIN_ATTEMPTS - wrapped.failed_attempts
+            ));
+        }
+    };
+
+    // A master password change leaves a stale key behind; drop it
+    let encrypted_store = load_encrypted_store()?;
+    if decrypt_bytes(&encrypted_store.encrypted_data, &encrypted_store.nonce, &key).is_err() {
+        quick_unlock.take();
+        return Err("Quick unlock is out of date; unlock with your master password".to_string());
+    }
+
+    wrapped.failed_attempts = 0;
+    start_session(&key, "pin");
+    Ok(())
+}
+
+// Center on the monitor containing the pointer; falls back to the window
+// manager's idea of "center" (the primary monitor) if that can't be worked out
+fn center_panel(window: &tauri::WebviewWindow) {
+    let on_active_monitor = load_settings()
+        .map(|s| s.center_on_active_monitor)
+        .unwrap_or(true);
+
+    let target = on_active_monitor
+        .then(|| {
+            let cursor = window.cursor_position().ok()?;
+            let monitors = window.available_monitors().ok()?;
+            monitors.into_iter().find(|monitor| {
+                let position = monitor.position();
+                let size = monitor.size();
+                cursor.x >= f64::from(position.x)
+                    && cursor.x < f64::from(position.x) + f64::from(size.width)
+                    && cursor.y >= f64::from(position.y)
+                    && cursor.y < f64::from(position.y) + f64::from(size.height)
+            })
+        })
+        .flatten();
+
+    match (target, window.outer_size()) {
+        (Some(monitor), Ok(size)) => {
+            let x = monitor.position().x + (monitor.size().width as i32 - size.width as i32) / 2;
+            let y = monitor.position().y + (monitor.size().height as i32 - size.height as i32) / 2;
+            let _ = window.set_position(tauri::PhysicalPosition::new(x, y));
+        }
+        _ => {
+            let _ = window.center();
+        }
+    }
+}
+
+fn show_main_window(app_handle: &tauri::AppHandle) {
+    record_activity();
+    if let Some(window) = app_handle.get_webview_window("main") {
+        #[cfg(target_os = "macos")]
+        let _ = capture_current_focus();
+        let _ = window.show();
+        let _ = window.set_focus();
+        center_panel(&window);
+        let _ = window.emit("focus-search-input", ());
+    }
+}
+
+#[derive(serde::Serialize, Clone)]
+struct FillCandidate {
+    id: u32,
+    title: String,
+    username: String,
+    url: Option<String>,
+}
+
+// Same site means same registrable domain, so a login saved for
+// accounts.example.com is offered on www.example.com
+fn entries_for_url<'a>(entries: &'a [PasswordEntry], url: &str) -> Vec<&'a PasswordEntry> {
+    let Some(domain) = registrable_domain(url) else {
+        return Vec::new();
+    };
+
+    entries
+        .iter()
+        .filter(|entry| !entry.archived)
+        .filter(|entry| entry.url.as_deref().and_then(registrable_domain).as_deref() == Some(domain.as_str()))
+        .collect()
+}
+
+#[tauri::command]
+async fn find_entries_for_url(url: String, master_password: Option<String>) -> Result<Vec<FillCandidate>, String> {
+    let store = load_password_store(master_password.as_deref())?;
+
+    Ok(entries_for_url(&store.entries, &url)
+        .into_iter()
+        .map(|entry| FillCandidate {
+            id: entry.id,
+            title: entry.title.clone(),
+            username: entry.username.clone(),
+            url: entry.url.clone(),
+        })
+        .collect())
+}
+
+// Browser extension autofill over native messaging. The browser launches a
+// second copy of Cocoon as the host (see native_messaging_origin), which only
+// relays between the browser's stdin/stdout and the running app on loopback.
+// Both legs use the browser's framing: a native-endian u32 length followed by
+// that many bytes of JSON.
+const NATIVE_HOST_NAME: &str = "com.cocoon.password_manager";
+const MAX_NATIVE_MESSAGE_BYTES: u32 = 64 * 1024;
+const NATIVE_FILL_CONFIRM_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+static NATIVE_HOST_RUNNING: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    // Token -> where the user's answer goes (the chosen entry, or None to deny)
+    static ref PENDING_FILL_REQUESTS: Mutex<HashMap<String, std::sync::mpsc::Sender<Option<u32>>>> =
+        Mutex::new(HashMap::new());
+}
+
+#[derive(serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum NativeRequest {
+    Ping,
+    GetCredentials { url: String },
+}
+
+// Where the running app listens. Written to the app data dir, readable only by
+// this user, so the secret proves a connection comes from one of our relays.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct NativeEndpoint {
+    port: u16,
+    secret: String,
+}
+
+// First message a relay sends on its connection to the app
+#[derive(serde::Serialize, serde::Deserialize)]
+struct NativeHello {
+    secret: String,
+    origin: String,
+}
+
+// Sent to the UI, which asks the user before anything goes to the browser
+#[derive(serde::Serialize, Clone)]
+struct FillRequest {
+    token: String,
+    origin: String,
+    url: String,
+    domain: Option<String>,
+    candidates: Vec<FillCandidate>,
+}
+
+fn get_native_endpoint_path() -> Result<PathBuf, String> {
+    Ok(get_app_data_dir()?.join("native-host.json"))
+}
+
+// Chrome passes the calling extension's origin ("chrome-extension://<id>/");
+// Firefox passes the path of our host manifest and then the extension id.
+// Anything else is a normal launch.
+fn native_messaging_origin(args: &[String]) -> Option<String> {
+    if let Some(origin) = args.iter().find(|arg| arg.starts_with("chrome-extension://")) {
+        return Some(origin.clone());
+    }
+
+    match args {
+        [_, manifest, extension_id, ..]
+            if std::path::Path::new(manifest)
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy() == format!("{}.json", NATIVE_HOST_NAME)) =>
+        {
+            Some(extension_id.clone())
+        }
+        _ => None,
+    }
+}
+
+fn native_origin_allowed(origin: &str) -> bool {
+    load_settings()
+        .map(|s| s.native_messaging_origins.iter().any(|allowed| allowed == origin))
+        .unwrap_or(false)
+}
+
+fn read_native_message(input: &mut impl std::io::Read) -> std::io::Result<Option<Vec<u8>>> {
+    let mut length = [0u8; 4];
+    match input.read_exact(&mut length) {
+        Ok(()) => {}
+        // The browser closes stdin when the extension disconnects
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let length = u32::from_ne_bytes(length);
+    if length > MAX_NATIVE_MESSAGE_BYTES {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("message of {} bytes exceeds the limit", length),
+        ));
+    }
+
+    let mut message = vec![0u8; length as usize];
+    input.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+fn write_native_bytes(output: &mut impl std::io::Write, bytes: &[u8]) -> std::io::Result<()> {
+    output.write_all(&(bytes.len() as u32).to_ne_bytes())?;
+    output.write_all(bytes)?;
+    output.flush()
+}
+
+fn write_native_message(output: &mut impl std::io::Write, message: &serde_json::Value) -> std::io::Result<()> {
+    let bytes = zeroize::Zeroizing::new(serde_json::to_vec(message)?);
+    write_native_bytes(output, &bytes)
+}
+
+fn native_error(message: &str) -> serde_json::Value {
+    serde_json::json!({ "type": "error", "error": message })
+}
+
+// Blocks until the user picks an entry or denies; credentials only leave when
+// the vault is already unlocked and the user confirmed this exact request
+fn handle_credentials_request(app_handle: &tauri::AppHandle, origin: &str, url: String) -> serde_json::Value {
+    if SESSION_KEY.lock().unwrap().is_none() {
+        return native_error("Vault is locked");
+    }
+    let store = match load_password_store(None) {
+        Ok(store) => store,
+        Err(e) => return native_error(&e),
+    };
+
+    let candidates: Vec<FillCandidate> = entries_for_url(&store.entries, &url)
+        .into_iter()
+        .map(|entry| FillCandidate {
+            id: entry.id,
+            title: entry.title.clone(),
+            username: entry.username.clone(),
+            url: entry.url.clone(),
+        })
+        .collect();
+    if candidates.is_empty() {
+        return native_error("No matching entries");
+    }
+
+    let token = generate_token();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    PENDING_FILL_REQUESTS.lock().unwrap().insert(token.clone(), sender);
+
+    show_main_window(app_handle);
+    let _ = app_handle.emit(
+        "native-fill-request",
+        FillRequest {
+            token: token.clone(),
+            origin: origin.to_string(),
+            domain: registrable_domain(&url),
+            url,
+            candidates: candidates.clone(),
+        },
+    );
+
+    // A lock drops the sender, which reads as a denial
+    let answer = receiver.recv_timeout(NATIVE_FILL_CONFIRM_TIMEOUT);
+    PENDING_FILL_REQUESTS.lock().unwrap().remove(&token);
+
+    let entry_id = match answer {
+        Ok(Some(entry_id)) if candidates.iter().any(|c| c.id == entry_id) => entry_id,
+        Ok(Some(_)) => return native_error("Entry does not match this site"),
+        _ => return native_error("Request denied"),
+    };
+    match store.entries.iter().find(|entry| entry.id == entry_id) {
+        Some(entry) => serde_json::json!({
+            "type": "credentials",
+            "username": entry.username,
+            "password": entry.password,
+        }),
+        None => native_error("Entry not found"),
+    }
+}
+
+// One relay connection: authenticate it, check the extension is allowed,
+// then answer requests until the relay hangs up
+fn serve_native_connection(app_handle: &tauri::AppHandle, stream: std::net::TcpStream, secret: &str) {
+    use subtle::ConstantTimeEq;
+
+    let mut input = match stream.try_clone() {
+        Ok(input) => input,
+        Err(e) => {
+            tracing::warn!("Native messaging connection failed: {}", e);
+            return;
+        }
+    };
+    let mut output = stream;
+
+    let hello = read_native_message(&mut input)
+        .ok()
+        .flatten()
+        .and_then(|message| serde_json::from_slice::<NativeHello>(&message).ok());
+    let Some(hello) = hello else {
+        return;
+    };
+    if !bool::from(hello.secret.as_bytes().ct_eq(secret.as_bytes())) {
+        tracing::warn!("Rejected native messaging connection with a bad secret");
+        return;
+    }
+    if !native_origin_allowed(&hello.origin) {
+        tracing::warn!(origin = %hello.origin, "Rejected native messaging request from an unknown extension");
+        let _ = write_native_message(&mut output, &native_error("This extension is not allowed to use Cocoon"));
+        return;
+    }
+
+    loop {
+        let message = match read_native_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::warn!("Native messaging read failed: {}", e);
+                break;
+            }
+        };
+
+        let response = match serde_json::from_slice::<NativeRequest>(&message) {
+            Ok(NativeRequest::Ping) => serde_json::json!({ "type": "pong" }),
+            Ok(NativeRequest::GetCredentials { url }) => {
+                handle_credentials_request(app_handle, &hello.origin, url)
+            }
+            Err(e) => native_error(&format!("Invalid request: {}", e)),
+        };
+        if let Err(e) = write_native_message(&mut output, &response) {
+            tracing::warn!("Native messaging write failed: {}", e);
+            break;
+        }
+    }
+}
+
+// Loopback listener for browser relays. The UI calls this at startup, so a
+// second call is a no-op.
+#[tauri::command]
+async fn start_native_messaging_host(app_handle: tauri::AppHandle) -> Result<(), String> {
+    if NATIVE_HOST_RUNNING.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    let listen = || -> Result<(std::net::TcpListener, String), String> {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0))
+            .map_err(|e| format!("Failed to start native messaging host: {}", e))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to start native messaging host: {}", e))?
+            .port();
+
+        let endpoint = NativeEndpoint {
+            port,
+            secret: generate_token(),
+        };
+        let content = serde_json::to_vec(&endpoint)
+            .map_err(|e| format!("Failed to serialize native messaging endpoint: {}", e))?;
+        write_private_atomically(&get_native_endpoint_path()?, &content)?;
+        Ok((listener, endpoint.secret))
+    };
+    let (listener, secret) = match listen() {
+        Ok(listening) => listening,
+        Err(e) => {
+            NATIVE_HOST_RUNNING.store(false, Ordering::SeqCst);
+            return Err(e);
+        }
+    };
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().filter_map(Result::ok) {
+            let app_handle = app_handle.clone();
+            let secret = secret.clone();
+            std::thread::spawn(move || serve_native_connection(&app_handle, stream, &secret));
+        }
+    });
+    tracing::info!("Native messaging host started");
+    Ok(())
+}
+
+// The browser-launched copy: no window, just relays messages between the
+// browser and the running app
+fn run_native_messaging_relay(origin: String) {
+    if let Err(e) = relay_native_messages(&origin) {
+        tracing::warn!("Native messaging relay failed: {}", e);
+        let _ = write_native_message(&mut std::io::stdout().lock(), &native_error(&e));
+    }
+}
+
+fn relay_native_messages(origin: &str) -> Result<(), String> {
+    const NOT_RUNNING: &str = "Cocoon is not running";
+
+    let endpoint: NativeEndpoint = fs::read(get_native_endpoint_path()?)
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .ok_or_else(|| NOT_RUNNING.to_string())?;
+    let stream = std::net::TcpStream::connect(("127.0.0.1", endpoint.port)).map_err(|_| NOT_RUNNING.to_string())?;
+    let mut to_app = stream
+        .try_clone()
+        .map_err(|e| format!("Failed to connect to Cocoon: {}", e))?;
+    let mut from_app = stream;
+
+    let hello = NativeHello {
+        secret: endpoint.secret,
+        origin: origin.to_string(),
+    };
+    let hello = serde_json::to_value(&hello).map_err(|e| format!("Failed to serialize hello: {}", e))?;
+    write_native_message(&mut to_app, &hello).map_err(|_| NOT_RUNNING.to_string())?;
+
+    // Replies go back on their own thread: the app can take a minute to
+    // answer while the user decides
+    let replies = std::thread::spawn(move || {
+        let mut stdout = std::io::stdout().lock();
+        while let Ok(Some(message)) = read_native_message(&mut from_app) {
+            let message = zeroize::Zeroizing::new(message);
+            if write_native_bytes(&mut stdout, &message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut stdin = std::io::stdin().lock();
+    while let Some(message) = read_native_message(&mut stdin).map_err(|e| format!("Failed to read from browser: {}", e))? {
+        if write_native_bytes(&mut to_app, &message).is_err() {
+            break;
+        }
+    }
+    let _ = to_app.shutdown(std::net::Shutdown::Both);
+    let _ = replies.join();
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum BrowserFamily {
+    Chromium,
+    Firefox,
+}
+
+// Per-user manifest locations. Windows finds manifests through the registry
+// instead, so they're written next to the app data and registered.
+fn native_manifest_dirs() -> Result<Vec<(BrowserFamily, PathBuf)>, String> {
+    let mut dirs = Vec::new();
+
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(config) = dirs::config_dir() {
+            for browser in ["google-chrome", "chromium", "BraveSoftware/Brave-Browser", "microsoft-edge"] {
+                dirs.push((BrowserFamily::Chromium, config.join(browser).join("NativeMessagingHosts")));
             }
         }
+        if let Some(home) = dirs::home_dir() {
+            dirs.push((BrowserFamily::Firefox, home.join(".mozilla").join("native-messaging-hosts")));
+        }
+    }
 
-        #[cfg(not(target_os = "macos"))]
-        {
-            std::thread::sleep(std::time::Duration::from_millis(500));
-            simulate_typing(&entry.username)?;
-            simulate_tab()?;
-            std::thread::sleep(std::time::Duration::from_millis(100));
-            simulate_typing(&entry.password)?;
-            
-            // Optionally press Enter to login
-            if press_enter {
-                std::thread::sleep(std::time::Duration::from_millis(200));
-                simulate_enter()?;
+    #[cfg(target_os = "macos")]
+    {
+        if let Some(support) = dirs::data_dir() {
+            for browser in ["Google/Chrome", "Chromium", "BraveSoftware/Brave-Browser", "Microsoft Edge"] {
+                dirs.push((BrowserFamily::Chromium, support.join(browser).join("NativeMessagingHosts")));
             }
+            dirs.push((BrowserFamily::Firefox, support.join("Mozilla").join("NativeMessagingHosts")));
         }
-    } else {
-        return Err("Entry not found".to_string());
     }
 
-    Ok(())
+    #[cfg(target_os = "windows")]
+    {
+        let dir = get_app_data_dir()?.join("native-messaging");
+        dirs.push((BrowserFamily::Chromium, dir.join("chromium")));
+        dirs.push((BrowserFamily::Firefox, dir.join("firefox")));
+    }
+
+    Ok(dirs)
 }
 
-#[tauri::command(async)]
-async fn search_entries(
-    query: String,
-    master_password: String,
-) -> Result<Vec<PasswordEntry>, String> {
-    let store = load_password_store(&master_password)?;
+fn native_manifest(family: BrowserFamily, host_path: &str, origins: &[String]) -> serde_json::Value {
+    let (key, origins): (&str, Vec<&String>) = match family {
+        BrowserFamily::Chromium => (
+            "allowed_origins",
+            origins.iter().filter(|o| o.starts_with("chrome-extension://")).collect(),
+        ),
+        BrowserFamily::Firefox => (
+            "allowed_extensions",
+            origins.iter().filter(|o| !o.starts_with("chrome-extension://")).collect(),
+        ),
+    };
 
-    if query.is_empty() {
-        return Ok(store.entries);
+    serde_json::json!({
+        "name": NATIVE_HOST_NAME,
+        "description": "Cocoon Password Manager",
+        "path": host_path,
+        "type": "stdio",
+        key: origins,
+    })
+}
+
+#[cfg(target_os = "windows")]
+fn register_native_manifest(family: BrowserFamily, manifest_path: &std::path::Path) -> Result<(), String> {
+    let roots: &[&str] = match family {
+        BrowserFamily::Chromium => &[r"Software\Google\Chrome", r"Software\Microsoft\Edge"],
+        BrowserFamily::Firefox => &[r"Software\Mozilla"],
+    };
+    for root in roots {
+        let key = format!(r"HKCU\{}\NativeMessagingHosts\{}", root, NATIVE_HOST_NAME);
+        let status = std::process::Command::new("reg")
+            .args(["add", &key, "/ve", "/t", "REG_SZ", "/d"])
+            .arg(manifest_path)
+            .arg("/f")
+            .status()
+            .map_err(|e| format!("Failed to register native messaging host: {}", e))?;
+        if !status.success() {
+            return Err(format!("Failed to register native messaging host under {}", key));
+        }
     }
+    Ok(())
+}
 
-    let filtered_entries: Vec<PasswordEntry> = store
-        .entries
-        .into_iter()
-        .filter(|entry| {
-            entry.title.to_lowercase().contains(&query.to_lowercase())
-                || entry
-                    .username
-                    .to_lowercase()
-                    .contains(&query.to_lowercase())
-                || entry.url.as_ref().map_or(false, |url| {
-                    url.to_lowercase().contains(&query.to_lowercase())
-                })
-        })
+// Writes the host manifest for every supported browser and remembers which
+// extensions may ask for credentials. Returns the manifests written.
+#[tauri::command]
+async fn install_native_messaging_host(allowed_origins: Vec<String>) -> Result<Vec<String>, String> {
+    let allowed_origins: Vec<String> = allowed_origins
+        .iter()
+        .map(|origin| origin.trim().to_string())
+        .filter(|origin| !origin.is_empty())
         .collect();
+    if allowed_origins.is_empty() {
+        return Err("Add at least one browser extension".to_string());
+    }
+    for origin in &allowed_origins {
+        let valid = match origin.strip_prefix("chrome-extension://") {
+            Some(id) => id
+                .strip_suffix('/')
+                .is_some_and(|id| id.len() == 32 && id.chars().all(|c| ('a'..='p').contains(&c))),
+            None => !origin.contains(['/', ' ']),
+        };
+        if !valid {
+            return Err(format!("'{}' is not a browser extension id or origin", origin));
+        }
+    }
 
-    Ok(filtered_entries)
-}
+    let host_path = std::env::current_exe()
+        .map_err(|e| format!("Failed to locate Cocoon: {}", e))?
+        .to_string_lossy()
+        .to_string();
 
-#[tauri::command]
-async fn add_entry(
-    title: String,
-    username: String,
-    password: String,
-    url: Option<String>,
-    notes: Option<String>,
-    master_password: String,
-) -> Result<u32, String> {
-    let mut store = load_password_store(&master_password)?;
-    let password_strength = calculate_password_strength(&password);
+    let mut written = Vec::new();
+    for (family, dir) in native_manifest_dirs()? {
+        // Only install for browsers that are actually set up on this machine
+        if !cfg!(target_os = "windows") && dir.parent().is_some_and(|parent| !parent.is_dir()) {
+            continue;
+        }
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
 
-    let entry = PasswordEntry {
-        id: store.next_id,
-        title,
-        username,
-        password,
-        url,
-        notes,
-        created_at: chrono::Utc::now().to_rfc3339(),
-        modified_at: chrono::Utc::now().to_rfc3339(),
-        password_strength,
-    };
+        let manifest = native_manifest(family, &host_path, &allowed_origins);
+        let content = serde_json::to_string_pretty(&manifest)
+            .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+        let path = dir.join(format!("{}.json", NATIVE_HOST_NAME));
+        write_atomically(&path, content.as_bytes())?;
+        #[cfg(target_os = "windows")]
+        register_native_manifest(family, &path)?;
 
-    let entry_id = entry.id;
-    store.entries.push(entry);
-    store.next_id += 1;
+        written.push(path.to_string_lossy().to_string());
+    }
 
-    save_password_store(&store, &master_password)?;
+    let mut settings = load_settings()?;
+    settings.native_messaging_origins = allowed_origins;
+    save_settings(&settings)?;
 
-    Ok(entry_id)
+    tracing::info!(manifests = written.len(), "Native messaging host installed");
+    Ok(written)
 }
 
+// entry_id None denies the request
 #[tauri::command]
-async fn update_entry(
-    id: u32,
-    title: String,
-    username: String,
-    password: String,
-    url: Option<String>,
-    notes: Option<String>,
-    master_password: String,
-) -> Result<(), String> {
-    let mut store = load_password_store(&master_password)?;
+async fn confirm_native_fill(token: String, entry_id: Option<u32>) -> Result<(), String> {
+    let sender = PENDING_FILL_REQUESTS
+        .lock()
+        .unwrap()
+        .remove(&token)
+        .ok_or_else(|| "Fill request expired".to_string())?;
+
+    sender
+        .send(entry_id)
+        .map_err(|_| "Fill request expired".to_string())
+}
 
-    if let Some(entry) = store.entries.iter_mut().find(|e| e.id == id) {
-        entry.title = title;
-        entry.username = username;
-        entry.password = password.clone();
-        entry.url = url;
-        entry.notes = notes;
-        entry.modified_at = chrono::Utc::now().to_rfc3339();
-        entry.password_strength = calculate_password_strength(&password);
+// Drop the cached vault key and every other in-memory secret, then tell the
+// UI to return to the unlock screen
+fn lock_session(app_handle: &tauri::AppHandle) {
+    clear_session_key();
+    REVEAL_SLOTS.lock().unwrap().clear();
+    PENDING_IMPORTS.lock().unwrap().clear();
+    SHARE_SERVERS.lock().unwrap().clear();
+    PENDING_PASSWORD_STEPS.lock().unwrap().clear();
+    PENDING_FILL_REQUESTS.lock().unwrap().clear();
+    // Grants hold the master password itself
+    SESSION_GRANTS.lock().unwrap().clear();
+    wipe_own_clipboard(app_handle);
 
-        save_password_store(&store, &master_password)?;
-        Ok(())
-    } else {
-        Err("Entry not found".to_string())
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let _ = window.hide();
     }
+    let _ = app_handle.emit("vault-locked", ());
+    tracing::info!("Vault locked");
 }
 
+// There's no undo stack yet; once one exists it must be cleared here too
 #[tauri::command]
-async fn delete_entry(id: u32, master_password: String) -> Result<(), String> {
-    let mut store = load_password_store(&master_password)?;
+async fn lock_vault(app_handle: tauri::AppHandle) -> Result<(), String> {
+    lock_session(&app_handle);
+    Ok(())
+}
 
-    if let Some(pos) = store.entries.iter().position(|e| e.id == id) {
-        store.entries.remove(pos);
-        save_password_store(&store, &master_password)?;
-        Ok(())
-    } else {
-        Err("Entry not found".to_string())
+fn clear_session_key() {
+    // Dropping the Zeroizing wrapper wipes the key bytes
+    SESSION_KEY.lock().unwrap().take();
+    UNLOCKED_STORE.lock().unwrap().take();
+}
+
+fn handle_tray_menu(app_handle: &tauri::AppHandle, id: &str) {
+    match id {
+        "show" => show_main_window(app_handle),
+        "lock" => lock_session(app_handle),
+        "generate" => {
+            let config = PasswordGenConfig {
+                length: 20,
+                include_uppercase: true,
+                include_lowercase: true,
+                include_numbers: true,
+                include_symbols: true,
+                ..Default::default()
+            };
+            let copied = generate_from_config(&config)
+                .and_then(|password| write_clipboard(app_handle, password));
+            if let Err(e) = copied {
+                tracing::error!("Failed to copy generated password: {}", e);
+            }
+        }
+        "settings" => {
+            show_main_window(app_handle);
+            let _ = app_handle.emit("open-settings", ());
+        }
+        "quit" => app_handle.exit(0),
+        _ => {}
     }
 }
 
-#[tauri::command]
-async fn get_entry_by_id(id: u32, master_password: String) -> Result<PasswordEntry, String> {
-    let store = load_password_store(&master_password)?;
+fn build_tray(app_handle: &tauri::AppHandle) -> tauri::Result<()> {
+    let menu = Menu::with_items(
+        app_handle,
+        &[
+            &MenuItem::with_id(app_handle, "show", "Show Cocoon", true, None::<&str>)?,
+            &MenuItem::with_id(app_handle, "lock", "Lock Vault", true, None::<&str>)?,
+            &MenuItem::with_id(app_handle, "generate", "Generate Password", true, None::<&str>)?,
+            &MenuItem::with_id(app_handle, "settings", "Settings", true, None::<&str>)?,
+            &PredefinedMenuItem::separator(app_handle)?,
+            &MenuItem::with_id(app_handle, "quit", "Quit", true, None::<&str>)?,
+        ],
+    )?;
 
-    store
-        .entries
-        .into_iter()
-        .find(|entry| entry.id == id)
-        .ok_or_else(|| "Entry not found".to_string())
+    let mut builder = TrayIconBuilder::with_id("main")
+        .tooltip("Cocoon Password Manager")
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(|app, event| handle_tray_menu(app, event.id().as_ref()));
+    if let Some(icon) = app_handle.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app_handle)?;
+    Ok(())
 }
 
 #[tauri::command]
-async fn hide_window(app_handle: tauri::AppHandle) -> Result<(), String> {
-    if let Some(window) = app_handle.get_webview_window("main") {
-        let _ = window.hide();
-    }
-    Ok(())
+async fn set_center_on_active_monitor(enabled: bool) -> Result<(), String> {
+    let mut settings = load_settings()?;
+    settings.center_on_active_monitor = enabled;
+    save_settings(&settings)
 }
 
 #[tauri::command]
-async fn generate_password(
-    length: usize,
-    include_uppercase: bool,
-    include_lowercase: bool,
-    include_numbers: bool,
-    include_symbols: bool,
-) -> Result<String, String> {
-    if length < 4 || length > 128 {
-        return Err("Password length must be between 4 and 128 characters".to_string());
-    }
+async fn set_trim_on_fill(enabled: bool) -> Result<(), String> {
+    let mut settings = load_settings()?;
+    settings.trim_on_fill = enabled;
+    save_settings(&settings)
+}
 
-    let mut charset = String::new();
-    if include_lowercase {
-        charset.push_str("abcdefghijklmnopqrstuvwxyz");
+// The global shortcut keeps working either way, so hiding the tray is safe
+#[tauri::command]
+async fn set_tray_visible(visible: bool, app_handle: tauri::AppHandle) -> Result<(), String> {
+    let has_tray = app_handle.tray_by_id("main").is_some();
+
+    if visible && !has_tray {
+        build_tray(&app_handle).map_err(|e| format!("Failed to create tray icon: {}", e))?;
+    } else if !visible && has_tray {
+        app_handle.remove_tray_by_id("main");
     }
-    if include_uppercase {
-        charset.push_str("ABCDEFGHIJKLMNOPQRSTUVWXYZ");
+
+    let mut settings = load_settings()?;
+    settings.show_tray = visible;
+    save_settings(&settings)
+}
+
+// A bare Escape can't be registered globally without swallowing it in every
+// other app, so the default is a chord that nothing common binds
+const DEFAULT_PANIC_SHORTCUT: &str = "CommandOrControl+Shift+L";
+
+lazy_static::lazy_static! {
+    static ref PANIC_SHORTCUT: Mutex<Option<tauri_plugin_global_shortcut::Shortcut>> = Mutex::new(None);
+}
+
+fn parse_shortcut(shortcut: &str) -> Result<tauri_plugin_global_shortcut::Shortcut, String> {
+    shortcut
+        .parse()
+        .map_err(|e| format!("Invalid shortcut '{}': {}", shortcut, e))
+}
+
+fn is_panic_shortcut(shortcut: &tauri_plugin_global_shortcut::Shortcut) -> bool {
+    PANIC_SHORTCUT.lock().unwrap().as_ref() == Some(shortcut)
+}
+
+// Hides unconditionally, so an open editor can't keep the window on screen
+fn panic_lock(app_handle: &tauri::AppHandle) {
+    QUICK_UNLOCK.lock().unwrap().take();
+    lock_session(app_handle);
+
+    if let Err(e) = app_handle.clipboard().clear() {
+        tracing::warn!("Failed to clear clipboard: {}", e);
     }
-    if include_numbers {
-        charset.push_str("0123456789");
+    tracing::info!("Panic shortcut triggered");
+}
+
+#[tauri::command]
+async fn set_panic_shortcut(
+    shortcut: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    use tauri_plugin_global_shortcut::GlobalShortcutExt;
+
+    let new_shortcut = shortcut.as_deref().map(parse_shortcut).transpose()?;
+    let global_shortcut = app_handle.global_shortcut();
+
+    // The shortcut handler reads PANIC_SHORTCUT, so don't hold it while
+    // talking to the global shortcut plugin
+    let old = PANIC_SHORTCUT.lock().unwrap().take();
+    if let Some(old) = old {
+        let _ = global_shortcut.unregister(old);
     }
-    if include_symbols {
-        charset.push_str("!@#$%^&*()_+-=[]{}|;:,.<>?");
+    if let Some(new_shortcut) = new_shortcut {
+        if let Err(e) = global_shortcut.register(new_shortcut) {
+            if let Some(old) = old {
+                if global_shortcut.register(old).is_ok() {
+                    *PANIC_SHORTCUT.lock().unwrap() = Some(old);
+                }
+            }
+            return Err(format!("Failed to register panic shortcut: {}", e));
+        }
+        *PANIC_SHORTCUT.lock().unwrap() = Some(new_shortcut);
     }
 
-    if charset.is_empty() {
-        return Err("At least one character type must be selected".to_string());
-    }
+    let mut settings = load_settings()?;
+    settings.panic_shortcut = shortcut;
+    save_settings(&settings)
+}
 
-    let chars: Vec<char> = charset.chars().collect();
-    let mut password = String::new();
-    let mut rng = OsRng;
+lazy_static::lazy_static! {
+    static ref ENTRY_HOTKEYS: Mutex<HashMap<tauri_plugin_global_shortcut::Shortcut, u32>> = Mutex::new(HashMap::new());
+}
+
+fn entry_for_hotkey(shortcut: &tauri_plugin_global_shortcut::Shortcut) -> Option<u32> {
+    ENTRY_HOTKEYS.lock().unwrap().get(shortcut).copied()
+}
 
-    for _ in 0..length {
-        let idx = (rng.next_u32() as usize) % chars.len();
-        password.push(chars[idx]);
+// Autofill straight into the focused app; only surface Cocoon when the
+// session is locked and a master password is needed first
+fn run_entry_hotkey(app_handle: &tauri::AppHandle, entry_id: u32) {
+    if SESSION_KEY.lock().unwrap().is_none() {
+        show_main_window(app_handle);
+        let _ = app_handle.emit("unlock-for-entry-hotkey", entry_id);
+        return;
     }
 
-    Ok(password)
+    #[cfg(target_os = "macos")]
+    let _ = capture_current_focus();
+
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = auto_fill_credentials_spotlight(entry_id, None, app_handle).await {
+            tracing::warn!(entry_id, "Entry hotkey autofill failed: {}", e);
+        }
+    });
 }
 
 #[tauri::command]
-async fn export_vault(export_password: String, master_password: String) -> Result<String, String> {
-    let store = load_password_store(&master_password)?;
-    let export_data = serde_json::to_string_pretty(&store)
-        .map_err(|e| format!("Failed to serialize vault: {}", e))?;
+async fn assign_entry_hotkey(
+    entry_id: u32,
+    shortcut_string: String,
+    master_password: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    use tauri_plugin_global_shortcut::GlobalShortcutExt;
+
+    find_entry(entry_id, master_password.as_deref())?.ok_or_else(|| "Entry not found".to_string())?;
+    let shortcut = parse_shortcut(&shortcut_string)?;
+    let global_shortcut = app_handle.global_shortcut();
+
+    // The shortcut handler looks hotkeys up through the same lock, so it is
+    // never held across register/unregister
+    let previous = {
+        let mut hotkeys = ENTRY_HOTKEYS.lock().unwrap();
+        let previous = hotkeys
+            .iter()
+            .find(|(_, id)| **id == entry_id)
+            .map(|(shortcut, _)| *shortcut);
+        if let Some(previous) = previous {
+            hotkeys.remove(&previous);
+        }
+        previous
+    };
+    if let Some(previous) = previous {
+        let _ = global_shortcut.unregister(previous);
+    }
 
-    // Encrypt export with provided password
-    let salt = SaltString::generate(&mut OsRng);
-    let key = generate_key_from_password(&export_password, salt.as_str().as_bytes())?;
-    let (encrypted_data, nonce) = encrypt_data(&export_data, &key)?;
+    // Fails if the combo is taken by the main/panic shortcut or another entry
+    if let Err(e) = global_shortcut.register(shortcut) {
+        if let Some(previous) = previous {
+            if global_shortcut.register(previous).is_ok() {
+                ENTRY_HOTKEYS.lock().unwrap().insert(previous, entry_id);
+            }
+        }
+        return Err(format!("Failed to register hotkey: {}", e));
+    }
+    ENTRY_HOTKEYS.lock().unwrap().insert(shortcut, entry_id);
 
-    let export_structure = serde_json::json!({
-        "version": 1,
-        "encrypted_data": encrypted_data,
-        "nonce": nonce,
-        "salt": general_purpose::STANDARD.encode(salt.as_str().as_bytes()),
-        "exported_at": chrono::Utc::now().to_rfc3339()
-    });
+    let mut settings = load_settings()?;
+    settings.entry_hotkeys.insert(entry_id, shortcut_string);
+    save_settings(&settings)
+}
 
-    serde_json::to_string_pretty(&export_structure)
-        .map_err(|e| format!("Failed to serialize export: {}", e))
+#[tauri::command]
+async fn remove_entry_hotkey(entry_id: u32, app_handle: tauri::AppHandle) -> Result<(), String> {
+    release_entry_hotkeys(&app_handle, |id| id != entry_id)
+}
+
+// Unregisters and forgets every entry hotkey whose entry fails `keep`
+fn release_entry_hotkeys(app_handle: &tauri::AppHandle, keep: impl Fn(u32) -> bool) -> Result<(), String> {
+    use tauri_plugin_global_shortcut::GlobalShortcutExt;
+
+    let released: Vec<_> = {
+        let mut hotkeys = ENTRY_HOTKEYS.lock().unwrap();
+        let released = hotkeys
+            .iter()
+            .filter(|(_, id)| !keep(**id))
+            .map(|(shortcut, _)| *shortcut)
+            .collect();
+        hotkeys.retain(|_, id| keep(*id));
+        released
+    };
+    for shortcut in released {
+        let _ = app_handle.global_shortcut().unregister(shortcut);
+    }
+
+    let mut settings = load_settings()?;
+    let before = settings.entry_hotkeys.len();
+    settings.entry_hotkeys.retain(|id, _| keep(*id));
+    if settings.entry_hotkeys.len() == before {
+        return Ok(());
+    }
+    save_settings(&settings)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    if let Err(e) = init_logging() {
+        eprintln!("{}", e);
+    }
+
+    // Launched by a browser for its extension: relay only, no window
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(origin) = native_messaging_origin(&args) {
+        run_native_messaging_relay(origin);
+        return;
+    }
+
+    tracing::info!(backend = INPUT_BACKEND, "Cocoon starting");
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_clipboard_manager::init())
@@ -1146,6 +8936,7 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             setup_master_password,
             verify_master_password,
+            unlock_vault,
             has_master_password,
             search_entries,
             add_entry,
@@ -1161,16 +8952,141 @@ pub fn run() {
     auto_fill_and_login_spotlight,
     press_enter_after_autofill,
     auto_fill_credentials_spotlight_with_login,
-            focus_search_input
+            focus_search_input,
+            get_autostart,
+            set_autostart,
+            add_passkey,
+            get_passkey,
+            test_autofill,
+            import_vault,
+            import_csv,
+            cancel_autofill,
+            get_log_path,
+            set_log_level,
+            confirm_password_matches,
+            import_apple_passwords,
+            resize_for_results,
+            scan_vault_against_hashset,
+            set_vault_directory,
+            request_reveal,
+            consume_reveal,
+            find_replace,
+            generate_password_min_strength,
+            windows_hello_status,
+            enable_windows_hello_unlock,
+            unlock_with_windows_hello,
+            get_entry_count,
+            parse_otpauth_uri,
+            decode_totp_from_qr,
+            get_vault_path,
+            export_entry,
+            import_entry,
+            unlock_and_audit,
+            set_locked_preview,
+            locked_preview_search,
+            set_tray_visible,
+            issue_session_token,
+            rekey_with_session,
+            set_panic_shortcut,
+            generate_password_simple,
+            prune_password_history,
+            setup_master_password_with_keyfile,
+            unlock_with_keyfile,
+            autofill_step,
+            assign_entry_hotkey,
+            remove_entry_hotkey,
+            create_backup,
+            verify_backup,
+            restore_backup,
+            set_searchable_fields,
+            create_entry_with_generated_password,
+            set_allow_network_checks,
+            check_dead_urls,
+            export_printable,
+            compact_ids,
+            reveal_password_timed,
+            import_browser_csv,
+            can_simulate_input,
+            rotate_password,
+            set_center_on_active_monitor,
+            validate_master_password_candidate,
+            set_notes_sensitive,
+            reveal_notes,
+            vault_diagnostics,
+            set_trim_on_fill,
+            detect_whitespace_issues,
+            crypto_info,
+            totp_setup_qr,
+            repair_vault,
+            save_autofill_profile,
+            set_active_autofill_profile,
+            find_duplicate_titles,
+            list_entries_paged,
+            copy_to_clipboard,
+            set_clear_clipboard_on_hide,
+            lock_vault,
+            preview_import,
+            commit_import,
+            diff_vaults,
+            type_text_spotlight,
+            list_target_windows,
+            set_autofill_target,
+            get_unlock_audit,
+            clear_unlock_audit,
+            get_totp_code,
+            generate_username,
+            bulk_add_tag,
+            bulk_remove_tag,
+            check_vault_health,
+            self_test,
+            reorder_entries,
+            audit_missing_totp,
+            start_share_server,
+            stop_share_server,
+            normalize_totp_secret,
+            merge_entries,
+            set_suppress_auto_hide,
+            set_blur_hide_delay_ms,
+            export_vault_age,
+            import_vault_age,
+            kdf_cost_estimate,
+            set_custom_fields,
+            export_emergency_pdf,
+            clipboard_is_safe,
+            enable_quick_unlock,
+            disable_quick_unlock,
+            quick_unlock_enabled,
+            unlock_with_pin,
+            import_json,
+            password_entropy_report,
+            archive_entry,
+            unarchive_entry,
+            verify_app_integrity,
+            set_idle_timeout_mins,
+            set_hide_on_blur,
+            get_vault_policy,
+            set_vault_policy,
+            generate_passwords_bulk,
+            normalize_domain,
+            upgrade_vault_format,
+            find_entries_for_url,
+            start_native_messaging_host,
+            confirm_native_fill,
+            install_native_messaging_host
         ])
         .setup(|app| {
-            // Create tray icon
+            if let Err(e) = self_test() {
+                tracing::error!("{}", e);
+            }
+
+            spawn_idle_watchdog(app.handle().clone());
+
+            // Create tray icon unless the user prefers shortcut-only access
             #[cfg(desktop)]
             {
-                TrayIconBuilder::with_id("main")
-                    .tooltip("Cocoon Password Manager")
-                    .icon(app.default_window_icon().unwrap().clone())
-                    .build(app)?;
+                if load_settings().map(|s| s.show_tray).unwrap_or(true) {
+                    build_tray(app.handle())?;
+                }
             }
 
             // Setup enhanced global shortcut with focus capture
@@ -1182,7 +9098,15 @@ pub fn run() {
                 app.handle().plugin(
                     tauri_plugin_global_shortcut::Builder::new()
                         .with_handler(move |_app, received_shortcut, event| {
-                            if received_shortcut == &shortcut {
+                            if is_panic_shortcut(received_shortcut) {
+                                if matches!(event.state(), ShortcutState::Pressed) {
+                                    panic_lock(_app);
+                                }
+                            } else if let Some(entry_id) = entry_for_hotkey(received_shortcut) {
+                                if matches!(event.state(), ShortcutState::Pressed) {
+                                    run_entry_hotkey(_app, entry_id);
+                                }
+                            } else if received_shortcut == &shortcut {
                                 match event.state() {
                                     ShortcutState::Pressed => {
                                         if let Some(window) = _app.get_webview_window("main") {
@@ -1190,7 +9114,7 @@ pub fn run() {
 
                                             if is_visible {
                                                 // Hide like Spotlight
-                                                let _ = window.hide();
+                                                hide_main_window(_app, &window);
                                             } else {
                                                 // Capture current focus before showing Cocoon
                                                 #[cfg(target_os = "macos")]
@@ -1200,13 +9124,13 @@ pub fn run() {
                                                 #[cfg(target_os = "macos")]
                                                 {
                                                     if let Err(e) = configure_spotlight_panel(&window) {
-                                                        eprintln!("Failed to configure Spotlight panel: {}", e);
+                                                        tracing::warn!("Failed to configure Spotlight panel: {}", e);
                                                     }
                                                 }
 
                                                 // Show and position like Spotlight
                                                 let _ = window.show();
-                                                let _ = window.center();
+                                                center_panel(&window);
                                                 let _ = window.set_focus();
 
                                                 #[cfg(target_os = "macos")]
@@ -1235,6 +9159,37 @@ pub fn run() {
                 )?;
 
                 app.global_shortcut().register(shortcut)?;
+
+                let panic_shortcut = load_settings()
+                    .unwrap_or_default()
+                    .panic_shortcut
+                    .map(|s| parse_shortcut(&s))
+                    .transpose();
+                match panic_shortcut {
+                    Ok(Some(panic_shortcut)) => {
+                        match app.global_shortcut().register(panic_shortcut) {
+                            Ok(()) => *PANIC_SHORTCUT.lock().unwrap() = Some(panic_shortcut),
+                            Err(e) => tracing::warn!("Failed to register panic shortcut: {}", e),
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("{}", e),
+                }
+
+                for (entry_id, shortcut_string) in load_settings().unwrap_or_default().entry_hotkeys {
+                    let registered = parse_shortcut(&shortcut_string).and_then(|hotkey| {
+                        app.global_shortcut()
+                            .register(hotkey)
+                            .map_err(|e| format!("Failed to register hotkey: {}", e))?;
+                        Ok(hotkey)
+                    });
+                    match registered {
+                        Ok(hotkey) => {
+                            ENTRY_HOTKEYS.lock().unwrap().insert(hotkey, entry_id);
+                        }
+                        Err(e) => tracing::warn!(entry_id, "{}", e),
+                    }
+                }
             }
 
             // Configure main window
@@ -1244,21 +9199,23 @@ pub fn run() {
                 #[cfg(target_os = "macos")]
                 {
                     if let Err(e) = configure_spotlight_panel(&window) {
-                        eprintln!("Failed to configure Spotlight panel: {}", e);
+                        tracing::warn!("Failed to configure Spotlight panel: {}", e);
                     }
                 }
 
                 let _ = window.hide();
             }
 
-            // Enable autostart
+            // Sync autostart with the user's setting (opt-in, off by default)
             #[cfg(desktop)]
             {
-                let autostart_manager = app.autolaunch();
-                if !autostart_manager.is_enabled().unwrap_or(false) {
-                    autostart_manager
-                        .enable()
-                        .expect("Failed to enable autostart");
+                let settings = load_settings().unwrap_or_else(|e| {
+                    tracing::error!("Failed to load settings: {}", e);
+                    AppSettings::default()
+                });
+
+                if let Err(e) = apply_autostart(app.handle(), settings.autostart) {
+                    tracing::error!("Failed to apply autostart setting: {}", e);
                 }
             }
 
@@ -1269,14 +9226,21 @@ pub fn run() {
         .run(|app_handle, event| match event {
             RunEvent::WindowEvent { label, event, .. } => match event {
                 WindowEvent::Focused(focused) => {
-                    if !focused {
+                    if !focused && effective_lock_policy().1 {
                         // Spotlight-like behavior: hide when losing focus
                         if let Some(window) = app_handle.get_webview_window(&label) {
                             let window_clone = window.clone();
+                            let app_clone = app_handle.clone();
+                            let delay_ms = load_settings()
+                                .map(|s| s.blur_hide_delay_ms)
+                                .unwrap_or(DEFAULT_BLUR_HIDE_DELAY_MS);
                             std::thread::spawn(move || {
-                                std::thread::sleep(std::time::Duration::from_millis(100));
-                                if !window_clone.is_focused().unwrap_or(false) {
-                                    let _ = window_clone.hide();
+                                std::thread::sleep(std::time::Duration::from_millis(delay_ms));
+                                // Focus may have come back (e.g. a native dialog closed)
+                                if !window_clone.is_focused().unwrap_or(false)
+                                    && !SUPPRESS_AUTO_HIDE.load(Ordering::SeqCst)
+                                {
+                                    hide_main_window(&app_clone, &window_clone);
                                 }
                             });
                         }
@@ -1286,7 +9250,7 @@ pub fn run() {
                     // Prevent closing, just hide
                     api.prevent_close();
                     if let Some(window) = app_handle.get_webview_window(&label) {
-                        let _ = window.hide();
+                        hide_main_window(app_handle, &window);
                     }
                 }
                 _ => {}
@@ -1294,19 +9258,332 @@ pub fn run() {
             RunEvent::TrayIconEvent(event) => {
                 #[cfg(desktop)]
                 match event {
-                    TrayIconEvent::Click { .. } => {
-                        if let Some(window) = app_handle.get_webview_window("main") {
-                            #[cfg(target_os = "macos")]
-                            let _ = capture_current_focus();
-                            let _ = window.show();
-                            let _ = window.set_focus();
-                            let _ = window.center();
-                            let _ = window.emit("focus-search-input", ());
-                        }
-                    }
+                    // Right click opens the context menu instead
+                    TrayIconEvent::Click {
+                        button: MouseButton::Left,
+                        button_state: MouseButtonState::Up,
+                        ..
+                    } => show_main_window(app_handle),
                     _ => {}
                 }
             }
             _ => {}
         });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn x11_fallback_keycodes_match_evdev() {
+        assert_eq!(x11_fallback_keycode(x11::keysym::XK_Return), Some(36));
+        assert_eq!(x11_fallback_keycode(x11::keysym::XK_Tab), Some(23));
+        assert_eq!(x11_fallback_keycode(x11::keysym::XK_Escape), None);
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn macos_virtual_keycodes_match_hitoolbox() {
+        assert_eq!(KVK_RETURN, 36);
+        assert_eq!(KVK_TAB, 48);
+    }
+
+    #[test]
+    fn normalize_url_matrix() {
+        let cases = [
+            ("Example.COM", "https://example.com"),
+            ("https://Example.com/", "https://example.com"),
+            ("www.example.com", "https://www.example.com"),
+            ("https://www.example.com/", "https://www.example.com"),
+            ("http://example.com:80/", "http://example.com"),
+            ("https://example.com:443", "https://example.com"),
+            ("https://example.com:8443/login/", "https://example.com:8443/login"),
+            ("ftp://files.example.com/pub/", "ftp://files.example.com/pub"),
+            ("https://example.com/search?q=1", "https://example.com/search?q=1"),
+            ("https://example.com/#/", "https://example.com/#/"),
+            ("localhost:3000", "https://localhost:3000"),
+            ("192.168.1.1", "https://192.168.1.1"),
+            ("10.0.0.1:8080/admin/", "https://10.0.0.1:8080/admin"),
+            ("http://[::1]:8080/", "http://[::1]:8080"),
+            ("  example.com  ", "https://example.com"),
+            ("Gmail", "Gmail"),
+            ("  work VPN ", "work VPN"),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(normalize_url(input), expected, "normalizing {:?}", input);
+        }
+    }
+
+    #[test]
+    fn normalize_optional_url_drops_blank() {
+        assert_eq!(normalize_optional_url(Some("   ".to_string())), None);
+        assert_eq!(normalize_optional_url(None), None);
+        assert_eq!(
+            normalize_optional_url(Some("example.com/".to_string())).as_deref(),
+            Some("https://example.com")
+        );
+    }
+
+    #[test]
+    fn seal_unseal_round_trip() {
+        let key = [7u8; 32];
+        let store_json = serde_json::to_string(&PasswordStore {
+            entries: vec![PasswordEntry {
+                id: 1,
+                title: "Example".to_string(),
+                username: "user@example.com".to_string(),
+                password: "correct horse battery staple".to_string(),
+                notes: Some("ünïcödé notes".to_string()),
+                ..Default::default()
+            }],
+            next_id: 2,
+            ..Default::default()
+        })
+        .unwrap();
+
+        for compress in [false, true] {
+            let (encrypted_data, nonce, compressed) = seal_with_compression(&store_json, &key, compress).unwrap();
+            assert_eq!(compressed, compress);
+
+            let plaintext = decrypt_bytes(&encrypted_data, &nonce, &key).unwrap();
+            assert_eq!(unseal_store_json(plaintext, compressed).unwrap(), store_json);
+
+            assert!(decrypt_bytes(&encrypted_data, &nonce, &[8u8; 32]).is_err());
+        }
+    }
+
+    #[cfg(unix)]
+    fn stored_kdf_params() -> argon2::Params {
+        let stored_hash = fs::read_to_string(get_master_hash_path().unwrap()).unwrap();
+        argon2::Params::try_from(&PasswordHash::new(&stored_hash).unwrap()).unwrap()
+    }
+
+    #[cfg(unix)]
+    fn stored_entry_titles(key: &[u8]) -> Vec<String> {
+        let encrypted_store = load_encrypted_store().unwrap();
+        let plaintext = decrypt_bytes(&encrypted_store.encrypted_data, &encrypted_store.nonce, key).unwrap();
+        let store: PasswordStore =
+            serde_json::from_str(&unseal_store_json(plaintext, encrypted_store.compressed).unwrap()).unwrap();
+        store.entries.into_iter().map(|entry| entry.title).collect()
+    }
+
+    // The only test that touches disk: everything resolves under a scratch HOME
+    #[cfg(unix)]
+    #[test]
+    fn kdf_upgrade_survives_interrupted_rekey() {
+        let home = std::env::temp_dir().join(format!("cocoon-kdf-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&home);
+        fs::create_dir_all(&home).unwrap();
+        std::env::set_var("HOME", &home);
+        std::env::set_var("XDG_DATA_HOME", home.join(".local").join("share"));
+        std::env::remove_var("SNAP_USER_DATA");
+        std::env::remove_var("APPIMAGE");
+
+        let password = "correct horse battery";
+        create_vault(password, None).unwrap();
+        let store_json = serde_json::to_string(&PasswordStore {
+            entries: vec![PasswordEntry {
+                id: 1,
+                title: "Example".to_string(),
+                ..Default::default()
+            }],
+            next_id: 2,
+            ..Default::default()
+        })
+        .unwrap();
+
+        // A vault from a build with a cheaper default
+        let weak = argon2::Params::new(8 * 1024, 1, 1, Some(32)).unwrap();
+        rekey_vault(password, &store_json, weak.clone()).unwrap();
+        assert_eq!(stored_kdf_params().m_cost(), weak.m_cost());
+        let weak_backup = backup_current_vault().unwrap();
+
+        // Unlocking upgrades it to at least the current defaults
+        let key = derive_master_key(password).unwrap();
+        let current = argon2::Params::default();
+        let upgraded = stored_kdf_params();
+        assert!(upgraded.m_cost() >= current.m_cost());
+        assert!(upgraded.t_cost() >= current.t_cost());
+        assert!(upgraded.p_cost() >= current.p_cost());
+        assert_eq!(stored_entry_titles(&key), ["Example"]);
+        assert!(!get_rekey_journal_path().unwrap().exists());
+
+        // A backup taken under the old cost still opens after the upgrade
+        let backup = load_backup_store(&weak_backup, Some(password)).unwrap();
+        assert_eq!(backup.entries[0].title, "Example");
+
+        // Crash after master.hash was rewritten but before the vault was:
+        // the journaled hash still matches the vault and must win
+        let hash_path = get_master_hash_path().unwrap();
+        fs::copy(&hash_path, get_rekey_journal_path().unwrap()).unwrap();
+        let orphaned_hash = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, weak)
+            .hash_password(password.as_bytes(), &SaltString::generate(&mut OsRng))
+            .unwrap()
+            .to_string();
+        fs::write(&hash_path, orphaned_hash).unwrap();
+
+        let key = derive_master_key(password).unwrap();
+        assert_eq!(stored_kdf_params().m_cost(), upgraded.m_cost());
+        assert_eq!(stored_entry_titles(&key), ["Example"]);
+        assert!(!get_rekey_journal_path().unwrap().exists());
+
+        assert_eq!(derive_master_key("wrong password").unwrap_err(), "Invalid master password");
+
+        let _ = fs::remove_dir_all(&home);
+    }
+
+    #[test]
+    fn scrub_log_message_hides_secrets() {
+        assert_eq!(
+            scrub_log_message("Autofill failed for token=abc123 password: hunter2"),
+            "Autofill failed for token=[redacted] password: [redacted]"
+        );
+        assert_eq!(
+            scrub_log_message("Decrypting with 3q2+7wAAAAB3NzaC1yc2EAAAADAQABAAABAQ=="),
+            "Decrypting with [redacted]"
+        );
+        assert_eq!(scrub_log_message("Vault saved (revision 12)"), "Vault saved (revision 12)");
+    }
+
+    fn custom_field(name: &str, value: &str, searchable: bool, sensitive: bool) -> CustomField {
+        CustomField {
+            name: name.to_string(),
+            value: value.to_string(),
+            searchable,
+            sensitive,
+        }
+    }
+
+    #[test]
+    fn custom_fields_matrix() {
+        let all_fields: std::collections::HashSet<FieldKind> =
+            [FieldKind::Title, FieldKind::Username, FieldKind::Url, FieldKind::Notes].into();
+
+        // (searchable, sensitive) -> (found by search, previewed, preview value)
+        let cases = [
+            (false, false, false, false, None),
+            (false, true, false, false, None),
+            (true, false, true, true, Some("A1B2C3")),
+            (true, true, true, true, Some(MASKED_VALUE)),
+        ];
+
+        for (searchable, sensitive, found, previewed, preview) in cases {
+            let entry = PasswordEntry {
+                title: "Router".to_string(),
+                custom_fields: vec![custom_field("Serial", "A1B2C3", searchable, sensitive)],
+                ..Default::default()
+            };
+            let context = format!("searchable={} sensitive={}", searchable, sensitive);
+
+            assert_eq!(search_match_weight(&entry, "b2c", &all_fields) > 0, found, "{}", context);
+
+            let previews = custom_field_previews(&entry.custom_fields);
+            assert_eq!(previews.len() == 1, previewed, "{}", context);
+            assert_eq!(previews.first().map(|f| f.value.as_str()), preview, "{}", context);
+            if let Some(field) = previews.first() {
+                assert_eq!(field.name, "Serial");
+                assert_eq!((field.searchable, field.sensitive), (searchable, sensitive));
+            }
+        }
+    }
+
+    #[test]
+    fn normalize_custom_fields_rejects_bad_names() {
+        let fields = normalize_custom_fields(vec![
+            custom_field("  PIN ", "1234", false, true),
+            custom_field("Account", "42", true, false),
+        ])
+        .unwrap();
+        assert_eq!(fields[0].name, "PIN");
+        assert_eq!(fields[1].name, "Account");
+
+        assert!(normalize_custom_fields(vec![custom_field("   ", "x", false, false)]).is_err());
+        assert_eq!(
+            normalize_custom_fields(vec![
+                custom_field("Pin", "1", false, false),
+                custom_field(" PIN", "2", false, false),
+            ])
+            .unwrap_err(),
+            "Duplicate custom field 'PIN'"
+        );
+    }
+
+    #[test]
+    fn native_messaging_origin_detects_browser_launches() {
+        let args = |list: &[&str]| list.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let chrome = "chrome-extension://abcdefghijklmnopabcdefghijklmnop/";
+
+        assert_eq!(native_messaging_origin(&args(&["cocoon", chrome])), Some(chrome.to_string()));
+        assert_eq!(
+            native_messaging_origin(&args(&["cocoon", "--parent-window=0", chrome])),
+            Some(chrome.to_string())
+        );
+        assert_eq!(
+            native_messaging_origin(&args(&[
+                "cocoon",
+                "/home/u/.mozilla/native-messaging-hosts/com.cocoon.password_manager.json",
+                "cocoon@example.org",
+            ])),
+            Some("cocoon@example.org".to_string())
+        );
+        assert_eq!(native_messaging_origin(&args(&["cocoon"])), None);
+        assert_eq!(native_messaging_origin(&args(&["cocoon", "/tmp/other.json", "x"])), None);
+    }
+
+    #[test]
+    fn native_manifest_splits_origins_by_browser() {
+        let origins = vec![
+            "chrome-extension://abcdefghijklmnopabcdefghijklmnop/".to_string(),
+            "cocoon@example.org".to_string(),
+        ];
+
+        let chromium = native_manifest(BrowserFamily::Chromium, "/opt/cocoon", &origins);
+        assert_eq!(chromium["name"], NATIVE_HOST_NAME);
+        assert_eq!(chromium["type"], "stdio");
+        assert_eq!(chromium["path"], "/opt/cocoon");
+        assert_eq!(chromium["allowed_origins"], serde_json::json!([origins[0]]));
+
+        let firefox = native_manifest(BrowserFamily::Firefox, "/opt/cocoon", &origins);
+        assert_eq!(firefox["allowed_extensions"], serde_json::json!([origins[1]]));
+        assert!(firefox.get("allowed_origins").is_none());
+    }
+
+    #[test]
+    fn bitwarden_totp_is_canonical_or_malformed() {
+        let export = serde_json::json!({
+            "encrypted": false,
+            "items": [
+                { "type": 1, "name": "Spaced", "login": { "password": "a", "totp": "jbsw y3dp ehpk 3pxp" } },
+                { "type": 1, "name": "Uri", "login": {
+                    "password": "b",
+                    "totp": "otpauth://totp/Example:me?secret=jbsw-y3dp-ehpk-3pxp&digits=8"
+                } },
+                { "type": 1, "name": "Broken", "login": { "password": "c", "totp": "not base32!" } },
+            ]
+        });
+
+        let (entries, malformed) = parse_bitwarden_json(&export.to_string()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].totp_secret.as_deref(), Some("JBSWY3DPEHPK3PXP"));
+        assert_eq!(entries[1].totp_secret.as_deref(), Some("JBSWY3DPEHPK3PXP"));
+        assert_eq!(entries[1].totp_digits, 8);
+        assert_eq!(malformed.len(), 1);
+        assert_eq!(malformed[0].line, 3);
+    }
+
+    #[test]
+    fn parse_otpauth_matches_entry_validation() {
+        let config = parse_otpauth("otpauth://totp/Example:me?secret=jbsw-y3dp%20ehpk-3pxp%3D%3D").unwrap();
+        assert_eq!(config.secret, "JBSWY3DPEHPK3PXP");
+        assert!(validate_totp_settings(TotpSettings::from(config)).is_ok());
+
+        let steam = parse_otpauth("steam://jbsw y3dp ehpk 3pxp").unwrap();
+        assert_eq!((steam.secret.as_str(), steam.digits), ("JBSWY3DPEHPK3PXP", 5));
+
+        assert!(parse_otpauth("otpauth://totp/Example:me?secret=JBSWY3DPEHPK3PXP&digits=4").is_err());
+        assert!(parse_otpauth("otpauth://totp/Example:me?secret=not-base32!").is_err());
+    }
+}
@@ -12,17 +12,49 @@ interface PasswordEntry {
   url?: string;
   notes?: string;
   created_at: string;
+  color?: string;
+  glyph?: string;
+  totp_secret?: string;
+  totp_digits: number;
+  totp_period: number;
+  totp_algorithm: string;
+}
+
+// Search rows carry no secrets; get_entry_by_id returns the full entry
+interface EntrySummary {
+  id: number;
+  title: string;
+  username: string;
+  url?: string;
+  modified_at: string;
+  color?: string;
+  glyph?: string;
 }
 
 type View = "search" | "add" | "edit";
 
+interface FillCandidate {
+  id: number;
+  title: string;
+  username: string;
+  url?: string;
+}
+
+interface FillRequest {
+  token: string;
+  origin: string;
+  url: string;
+  domain?: string;
+  candidates: FillCandidate[];
+}
+
 function App() {
   const [view, setView] = useState<View>("search");
   const [query, setQuery] = useState("");
-  const [entries, setEntries] = useState<PasswordEntry[]>([]);
+  const [entries, setEntries] = useState<EntrySummary[]>([]);
   const [selectedIndex, setSelectedIndex] = useState(0);
   const [loading, setLoading] = useState(false);
-  const [editingEntry, setEditingEntry] = useState<PasswordEntry | null>(null);
+  const [editingEntry, setEditingEntry] = useState<EntrySummary | PasswordEntry | null>(null);
   const [showPassword, setShowPassword] = useState(false);
   const [hasMasterPassword, setHasMasterPassword] = useState(false);
   const [masterPassword, setMasterPassword] = useState("");
@@ -32,6 +64,7 @@ function App() {
   const [isAuthenticated, setIsAuthenticated] = useState(false);
   const [editPasswordAuth, setEditPasswordAuth] = useState("");
   const [editAuthError, setEditAuthError] = useState("");
+  const [fillRequest, setFillRequest] = useState<FillRequest | null>(null);
 
   // Form state
   const [formData, setFormData] = useState({
@@ -63,6 +96,42 @@ function App() {
     };
   }, [isAuthenticated, view]);
 
+  // The backend refuses to unlock after a failed KDF self-test; say why up front
+  useEffect(() => {
+    invoke("self_test").catch((error) => {
+      console.error(error);
+      setAuthError(String(error));
+    });
+  }, []);
+
+  // Backend lock (tray menu, panic shortcut) drops the in-memory master password
+  useEffect(() => {
+    const unlisten = listen('vault-locked', () => {
+      setIsAuthenticated(false);
+      setMasterPassword("");
+      setEntries([]);
+      setView("search");
+      setFillRequest(null);
+    });
+
+    return () => {
+      unlisten.then(fn => fn());
+    };
+  }, []);
+
+  // A browser extension asked for a login; nothing is sent until the user picks one
+  useEffect(() => {
+    invoke("start_native_messaging_host").catch(console.error);
+
+    const unlisten = listen<FillRequest>('native-fill-request', (event) => {
+      setFillRequest(event.payload);
+    });
+
+    return () => {
+      unlisten.then(fn => fn());
+    };
+  }, []);
+
   // Enhanced focus management with better timing
   useEffect(() => {
     const focusInput = () => {
@@ -105,16 +174,15 @@ function App() {
     const searchEntries = async () => {
       setLoading(true);
       try {
-        const results = await invoke<PasswordEntry[]>("search_entries", {
+        const results = await invoke<EntrySummary[]>("search_entries", {
           query,
-          masterPassword,
         });
         setEntries(results);
         setSelectedIndex(0);
       } catch (error) {
         console.error("Search failed:", error);
         setEntries([]);
-        if (error === "Invalid master password") {
+        if (error === "Invalid master password" || error === "Vault is locked") {
           setIsAuthenticated(false);
           setAuthError("Invalid master password. Please re-enter.");
         }
@@ -195,12 +263,12 @@ function App() {
   const typeUsername = async (entryId: number) => {
     if (!isAuthenticated) return;
     try {
-      await invoke("type_username_spotlight", { entryId, masterPassword });
+      await invoke("type_username_spotlight", { entryId });
       showNotification("Username typed to active field");
     } catch (error) {
       console.error("Failed to type username:", error);
       showNotification("Failed to type username", "error");
-      if (error === "Invalid master password") {
+      if (error === "Invalid master password" || error === "Vault is locked") {
         setIsAuthenticated(false);
         setAuthError("Invalid master password. Please re-enter.");
       }
@@ -210,12 +278,12 @@ function App() {
   const typePassword = async (entryId: number) => {
     if (!isAuthenticated) return;
     try {
-      await invoke("type_password_spotlight", { entryId, masterPassword });
+      await invoke("type_password_spotlight", { entryId });
       showNotification("Password typed to active field");
     } catch (error) {
       console.error("Failed to type password:", error);
       showNotification("Failed to type password", "error");
-      if (error === "Invalid master password") {
+      if (error === "Invalid master password" || error === "Vault is locked") {
         setIsAuthenticated(false);
         setAuthError("Invalid master password. Please re-enter.");
       }
@@ -225,12 +293,12 @@ function App() {
   const autoFillCredentials = async (entryId: number) => {
     if (!isAuthenticated) return;
     try {
-      await invoke("auto_fill_credentials_spotlight_with_login", { entryId, masterPassword, pressEnter: true });
+      await invoke("auto_fill_credentials_spotlight_with_login", { entryId, pressEnter: true });
       showNotification("Credentials auto-filled to login form");
     } catch (error) {
       console.error("Failed to auto-fill credentials:", error);
       showNotification("Failed to auto-fill credentials", "error");
-      if (error === "Invalid master password") {
+      if (error === "Invalid master password" || error === "Vault is locked") {
         setIsAuthenticated(false);
         setAuthError("Invalid master password. Please re-enter.");
       }
@@ -258,6 +326,17 @@ function App() {
       setHasMasterPassword(exists);
       if (!exists) {
         setIsAuthenticated(false);
+        return;
+      }
+
+      const health = await invoke<{ status: string; source?: string }>(
+        "check_vault_health"
+      );
+      if (health.status === "RecoveredFromBackup") {
+        showNotification(
+          `Vault file was damaged and has been restored from ${health.source}`,
+          "error"
+        );
       }
     } catch (error) {
       console.error("Failed to check master password:", error);
@@ -301,14 +380,18 @@ function App() {
     }
 
     try {
-      await invoke("verify_master_password", {
-        password: masterPassword,
-      });
+      await invoke("unlock_vault", { masterPassword });
+      // Later calls run against the unlocked session
+      setMasterPassword("");
       setIsAuthenticated(true);
       setAuthError("");
     } catch (error) {
       console.log(error);
-      setAuthError("Invalid master password");
+      setAuthError(
+        String(error).startsWith("KDF self-test failed")
+          ? String(error)
+          : "Invalid master password"
+      );
       setMasterPassword("");
     }
   };
@@ -327,7 +410,7 @@ function App() {
     setEditingEntry(null);
   };
 
-  const openEditForm = (entry: PasswordEntry) => {
+  const openEditForm = (entry: EntrySummary) => {
     if (!isAuthenticated) return;
     setView("edit");
     setEditingEntry(entry);
@@ -336,7 +419,7 @@ function App() {
       username: entry.username,
       password: "",
       url: entry.url || "",
-      notes: entry.notes || "",
+      notes: "",
     });
     setShowPassword(false);
     setEditPasswordAuth("");
@@ -376,7 +459,6 @@ function App() {
           password: formData.password,
           url: formData.url.trim() || null,
           notes: formData.notes.trim() || null,
-          masterPassword,
         });
         showNotification("Password entry added successfully");
       } else if (view === "edit" && editingEntry) {
@@ -387,7 +469,17 @@ function App() {
           password: formData.password,
           url: formData.url.trim() || null,
           notes: formData.notes.trim() || null,
-          masterPassword,
+          color: editingEntry.color ?? null,
+          glyph: editingEntry.glyph ?? null,
+          // The form can only be submitted once the full entry was loaded
+          totp: "password" in editingEntry && editingEntry.totp_secret
+            ? {
+                secret: editingEntry.totp_secret,
+                digits: editingEntry.totp_digits,
+                period: editingEntry.totp_period,
+                algorithm: editingEntry.totp_algorithm,
+              }
+            : null,
         });
         showNotification("Password entry updated successfully");
       }
@@ -398,7 +490,7 @@ function App() {
     } catch (error) {
       console.error("Failed to save entry:", error);
       showNotification("Failed to save entry", "error");
-      if (error === "Invalid master password") {
+      if (error === "Invalid master password" || error === "Vault is locked") {
         setIsAuthenticated(false);
         setAuthError("Invalid master password. Please re-enter.");
       }
@@ -408,19 +500,18 @@ function App() {
   const handleDelete = async (id: number) => {
     if (!isAuthenticated) return;
     try {
-      await invoke("delete_entry", { id, masterPassword });
+      await invoke("delete_entry", { id });
       showNotification("Password entry deleted");
 
-      const results = await invoke<PasswordEntry[]>("search_entries", {
+      const results = await invoke<EntrySummary[]>("search_entries", {
         query: "",
-        masterPassword,
       });
       setEntries(results);
       setSelectedIndex(0);
     } catch (error) {
       console.error("Failed to delete entry:", error);
       showNotification("Failed to delete entry", "error");
-      if (error === "Invalid master password") {
+      if (error === "Invalid master password" || error === "Vault is locked") {
         setIsAuthenticated(false);
         setAuthError("Invalid master password. Please re-enter.");
       }
@@ -429,7 +520,11 @@ function App() {
 
   const generatePassword = async () => {
     try {
-      const password = await invoke<string>("generate_password", {
+      const { password } = await invoke<{
+        password: string;
+        strength: number;
+        estimated_crack_time: string;
+      }>("generate_password", {
         length: 16,
         includeUppercase: true,
         includeLowercase: true,
@@ -488,16 +583,23 @@ function App() {
         password: editPasswordAuth,
       });
       if (editingEntry) {
-        const entryWithPassword = await invoke<PasswordEntry>(
+        const entryWithPassword = await invoke<PasswordEntry & { notes_token?: string }>(
           "get_entry_by_id",
           {
             id: editingEntry.id,
             masterPassword: editPasswordAuth,
           }
         );
+        const notes = entryWithPassword.notes_token
+          ? await invoke<string>("reveal_notes", {
+              token: entryWithPassword.notes_token,
+            })
+          : undefined;
+        setEditingEntry(entryWithPassword);
         setFormData((prev) => ({
           ...prev,
           password: entryWithPassword.password,
+          notes: notes ?? entryWithPassword.notes ?? "",
         }));
       }
       setEditAuthError("");
@@ -597,6 +699,53 @@ function App() {
     );
   }
 
+  const answerFillRequest = async (entryId: number | null) => {
+    if (!fillRequest) return;
+    try {
+      await invoke("confirm_native_fill", { token: fillRequest.token, entryId });
+    } catch (error) {
+      console.error(error);
+    }
+    setFillRequest(null);
+  };
+
+  if (fillRequest) {
+    return (
+      <div className="app spotlight-style">
+        <div className="form-container">
+          <div className="form-header">
+            <h2>🌐 Fill {fillRequest.domain ?? fillRequest.url}?</h2>
+          </div>
+          <p>Requested by browser extension {fillRequest.origin}</p>
+          <div className="results-list">
+            {fillRequest.candidates.map((candidate) => (
+              <div
+                key={candidate.id}
+                className="result-item"
+                onClick={() => answerFillRequest(candidate.id)}
+              >
+                <div className="entry-content">
+                  <div className="entry-title">{candidate.title}</div>
+                  <div className="entry-username">{candidate.username}</div>
+                  {candidate.url && <div className="entry-url">{candidate.url}</div>}
+                </div>
+              </div>
+            ))}
+          </div>
+          <div className="form-actions">
+            <button
+              type="button"
+              className="spotlight-button secondary"
+              onClick={() => answerFillRequest(null)}
+            >
+              Deny
+            </button>
+          </div>
+        </div>
+      </div>
+    );
+  }
+
   if (view === "add" || view === "edit") {
     return (
       <div className="app spotlight-style">
@@ -783,6 +932,14 @@ function App() {
           >
             ➕
           </button>
+          <button
+            className="add-btn icon-btn"
+            onClick={() => invoke("lock_vault")}
+            title="Lock Vault"
+            disabled={!isAuthenticated}
+          >
+            🔒
+          </button>
         </div>
 
         <div className="shortcuts-bar">